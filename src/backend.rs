@@ -0,0 +1,1616 @@
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock, RwLock,
+    },
+    time::Duration,
+};
+
+use dashmap::DashMap;
+use ropey::Rope;
+use serde_json::Value;
+use tokio::{sync::Semaphore, task::JoinSet};
+use tower_lsp::{
+    jsonrpc::{Error, Result},
+    lsp_types::{
+        notification::{DidChangeConfiguration, DidChangeWatchedFiles, Notification},
+        request::WorkspaceDiagnosticRefresh,
+        ClientCapabilities, CodeActionKind, CodeActionOptions, CodeActionParams,
+        CodeActionProviderCapability, CodeActionResponse, CompletionOptions, CompletionParams,
+        CompletionResponse, ConfigurationItem, DiagnosticOptions, DiagnosticServerCapabilities,
+        DidChangeConfigurationParams, DidChangeTextDocumentParams, DidChangeWatchedFilesParams,
+        DidChangeWatchedFilesRegistrationOptions, DidChangeWorkspaceFoldersParams,
+        DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams,
+        DocumentDiagnosticParams, DocumentDiagnosticReport, DocumentDiagnosticReportResult,
+        ExecuteCommandOptions, ExecuteCommandParams, FileChangeType, FileSystemWatcher,
+        GlobPattern, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents,
+        HoverParams, HoverProviderCapability, InitializeParams, InitializeResult,
+        InitializedParams, Location, MarkupContent, MarkupKind, MessageType, OneOf, Position,
+        Range, Registration, RelatedFullDocumentDiagnosticReport,
+        RelatedUnchangedDocumentDiagnosticReport, SaveOptions, ServerCapabilities, ServerInfo,
+        TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions,
+        TextDocumentSyncSaveOptions, Unregistration, Url, WorkspaceDiagnosticParams,
+        WorkspaceDiagnosticReport, WorkspaceDiagnosticReportResult,
+        WorkspaceDocumentDiagnosticReport, WorkspaceFoldersServerCapabilities,
+        WorkspaceFullDocumentDiagnosticReport, WorkspaceServerCapabilities,
+        WorkspaceUnchangedDocumentDiagnosticReport,
+    },
+    Client, LanguageServer,
+};
+use tree_sitter::Tree;
+
+use crate::{
+    code_actions::{
+        disable_rule_actions, fix_all, is_requested, quick_fixes, replace_document,
+        SOURCE_FIX_ALL_TREE_SITTER_LINT,
+    },
+    commands::{self, Command},
+    config::{find_config_file, is_config_file, Config, CONFIG_FILE_NAME},
+    config_document,
+    diagnostics::{
+        config_error_to_diagnostic, pull_report, violation_to_diagnostic, FileReport, PullReport,
+    },
+    directives,
+    document::Document,
+    hover::rule_hover_markdown,
+    injections,
+    language::SupportedLanguage,
+    lint::{self, fix_all_with, Linter},
+    local_binary::{LocalBinary, LocalBinaryError},
+    plugins,
+    position::{position_to_char_index, range_to_lsp, PositionEncoding},
+    progress::WorkDoneProgressReporter,
+    rule::RuleMeta,
+    rule_definitions,
+    scheduler::{CancellationToken, LintScheduler},
+    settings::{self, Run, Settings},
+    violation::Violation,
+    workspace,
+};
+
+const SOURCE_FILE_WATCHER: &str = "tree-sitter-lint/source-file-watcher";
+/// Files with the extensions of the languages we support. (Those linted as
+/// some language only because of a config's `languages` aren't watched.)
+const SOURCE_FILE_GLOB: &str =
+    "**/*.{css,htm,html,js,mjs,cjs,jsx,py,pyi,rs,ts,mts,cts,tsx,yaml,yml}";
+
+#[derive(Clone)]
+pub struct Backend {
+    client: Client,
+    linter: Arc<Linter>,
+    documents: Arc<DashMap<Url, Document>>,
+    scheduler: Arc<LintScheduler>,
+    initialization_options: Arc<OnceLock<Value>>,
+    settings: Arc<RwLock<Arc<Settings>>>,
+    /// Settings for each workspace folder, as returned by
+    /// `workspace/configuration`.
+    folder_settings: Arc<DashMap<PathBuf, Arc<Settings>>>,
+    client_capabilities: Arc<OnceLock<ClientCapabilities>>,
+    workspace_folders: Arc<RwLock<Vec<PathBuf>>>,
+    configs: Arc<DashMap<PathBuf, Arc<Config>>>,
+    local_binaries: Arc<DashMap<PathBuf, Arc<LocalBinary>>>,
+    workspace_lint: Arc<Mutex<CancellationToken>>,
+    /// Whether the source file watcher is registered, which it is while
+    /// any workspace folder is being linted.
+    watching_source_files: Arc<AtomicBool>,
+    /// Results of linting files that aren't open, for
+    /// `workspace_diagnostic()` (and clearing them again).
+    file_reports: Arc<DashMap<Url, FileReport>>,
+    position_encoding: Arc<OnceLock<PositionEncoding>>,
+}
+
+impl Backend {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            linter: Arc::new(Linter::new(plugins::all())),
+            documents: Default::default(),
+            scheduler: Default::default(),
+            initialization_options: Default::default(),
+            settings: Default::default(),
+            folder_settings: Default::default(),
+            client_capabilities: Default::default(),
+            workspace_folders: Default::default(),
+            configs: Default::default(),
+            local_binaries: Default::default(),
+            workspace_lint: Default::default(),
+            watching_source_files: Default::default(),
+            file_reports: Default::default(),
+            position_encoding: Default::default(),
+        }
+    }
+
+    fn position_encoding(&self) -> PositionEncoding {
+        self.position_encoding.get().copied().unwrap_or_default()
+    }
+
+    /// The settings for the innermost workspace folder containing `uri`,
+    /// falling back to the global ones.
+    fn settings_for(&self, uri: &Url) -> Arc<Settings> {
+        uri.to_file_path()
+            .ok()
+            .and_then(|path| {
+                self.folder_settings
+                    .iter()
+                    .filter(|entry| path.starts_with(entry.key()))
+                    .max_by_key(|entry| entry.key().components().count())
+                    .map(|entry| entry.value().clone())
+            })
+            .unwrap_or_else(|| self.settings.read().unwrap().clone())
+    }
+
+    /// The innermost workspace folder containing `uri`.
+    fn workspace_folder_for(&self, uri: &Url) -> Option<PathBuf> {
+        let path = uri.to_file_path().ok()?;
+        self.workspace_folders
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|folder| path.starts_with(folder))
+            .max_by_key(|folder| folder.components().count())
+            .cloned()
+    }
+
+    /// Layers `values` over the `initializationOptions`, logging (and
+    /// falling back to the defaults) if the result isn't valid.
+    async fn merge_settings(&self, values: &[&Value]) -> Arc<Settings> {
+        let initialization_options = self.initialization_options.get();
+        match Settings::merged(
+            initialization_options
+                .into_iter()
+                .chain(values.iter().copied()),
+        ) {
+            Ok(settings) => Arc::new(settings),
+            Err(error) => {
+                self.client
+                    .log_message(MessageType::ERROR, format!("invalid settings: {error}"))
+                    .await;
+                Default::default()
+            }
+        }
+    }
+
+    fn supports_configuration(&self) -> bool {
+        self.client_capabilities
+            .get()
+            .and_then(|capabilities| capabilities.workspace.as_ref())
+            .and_then(|workspace| workspace.configuration)
+            .unwrap_or_default()
+    }
+
+    /// Pulls the global settings and those for each workspace folder with
+    /// `workspace/configuration`.
+    async fn fetch_settings(&self) {
+        let folders = self.workspace_folders.read().unwrap().clone();
+        let items = std::iter::once(None)
+            .chain(
+                folders
+                    .iter()
+                    .map(|folder| Url::from_directory_path(folder).ok()),
+            )
+            .map(|scope_uri| ConfigurationItem {
+                scope_uri,
+                section: Some(settings::SECTION.to_owned()),
+            })
+            .collect();
+        let values = match self.client.configuration(items).await {
+            Ok(values) => values,
+            Err(error) => {
+                self.client
+                    .log_message(
+                        MessageType::WARNING,
+                        format!("couldn't fetch settings: {error}"),
+                    )
+                    .await;
+                return;
+            }
+        };
+        let Some((global, values)) = values.split_first() else {
+            return;
+        };
+        *self.settings.write().unwrap() = self.merge_settings(&[global]).await;
+        self.folder_settings.clear();
+        for (folder, value) in folders.into_iter().zip(values) {
+            let settings = self.merge_settings(&[global, value]).await;
+            self.folder_settings.insert(folder, settings);
+        }
+    }
+
+    async fn config_for(&self, uri: &Url) -> Arc<Config> {
+        let Ok(path) = uri.to_file_path() else {
+            return Default::default();
+        };
+        let config_file = {
+            let workspace_folders = self.workspace_folders.read().unwrap();
+            find_config_file(&path, &workspace_folders)
+        };
+        let Some(config_file) = config_file else {
+            return Default::default();
+        };
+        if let Some(config) = self.configs.get(&config_file) {
+            return config.clone();
+        }
+        self.load_config(config_file).await
+    }
+
+    /// (Re)loads and caches `config_file`, publishing any error loading it as
+    /// a diagnostic on the config file itself.
+    async fn load_config(&self, config_file: PathBuf) -> Arc<Config> {
+        let (config, diagnostics) = match Config::load(&config_file) {
+            Ok(config) => (Arc::new(config), vec![]),
+            Err(error) => {
+                self.client
+                    .log_message(
+                        MessageType::ERROR,
+                        format!("{}: {error}", config_file.display()),
+                    )
+                    .await;
+                (Default::default(), vec![config_error_to_diagnostic(&error)])
+            }
+        };
+        self.configs.insert(config_file.clone(), config.clone());
+        // Open config files get validated as documents instead.
+        if let Ok(uri) = Url::from_file_path(&config_file) {
+            if !self.documents.contains_key(&uri) {
+                self.client
+                    .publish_diagnostics(uri, diagnostics, None)
+                    .await;
+            }
+        }
+        config
+    }
+
+    fn supports_file_watcher_registration(&self) -> bool {
+        self.client_capabilities
+            .get()
+            .and_then(|capabilities| capabilities.workspace.as_ref())
+            .and_then(|workspace| workspace.did_change_watched_files.as_ref())
+            .and_then(|did_change_watched_files| did_change_watched_files.dynamic_registration)
+            .unwrap_or_default()
+    }
+
+    async fn register_config_file_watcher(&self) {
+        if !self.supports_file_watcher_registration() {
+            return;
+        }
+
+        let registration = Registration {
+            id: "tree-sitter-lint/config-file-watcher".to_owned(),
+            method: DidChangeWatchedFiles::METHOD.to_owned(),
+            register_options: Some(
+                serde_json::to_value(DidChangeWatchedFilesRegistrationOptions {
+                    watchers: vec![FileSystemWatcher {
+                        glob_pattern: GlobPattern::String(format!("**/{CONFIG_FILE_NAME}")),
+                        kind: None,
+                    }],
+                })
+                .unwrap(),
+            ),
+        };
+        if let Err(error) = self.client.register_capability(vec![registration]).await {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("couldn't register config file watcher: {error}"),
+                )
+                .await;
+        }
+    }
+
+    /// (Un)registers the watcher for source files, through which files
+    /// linted as part of the workspace get linted again when they change.
+    async fn watch_source_files(&self, watch: bool) {
+        if self.watching_source_files.swap(watch, Ordering::Relaxed) == watch
+            || !self.supports_file_watcher_registration()
+        {
+            return;
+        }
+        let result = if watch {
+            let registration = Registration {
+                id: SOURCE_FILE_WATCHER.to_owned(),
+                method: DidChangeWatchedFiles::METHOD.to_owned(),
+                register_options: Some(
+                    serde_json::to_value(DidChangeWatchedFilesRegistrationOptions {
+                        watchers: vec![FileSystemWatcher {
+                            glob_pattern: GlobPattern::String(SOURCE_FILE_GLOB.to_owned()),
+                            kind: None,
+                        }],
+                    })
+                    .unwrap(),
+                ),
+            };
+            self.client.register_capability(vec![registration]).await
+        } else {
+            let unregistration = Unregistration {
+                id: SOURCE_FILE_WATCHER.to_owned(),
+                method: DidChangeWatchedFiles::METHOD.to_owned(),
+            };
+            self.client
+                .unregister_capability(vec![unregistration])
+                .await
+        };
+        if let Err(error) = result {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("couldn't update source file watcher: {error}"),
+                )
+                .await;
+        }
+    }
+
+    async fn register_configuration_change_notifications(&self) {
+        let supports_dynamic_registration = self
+            .client_capabilities
+            .get()
+            .and_then(|capabilities| capabilities.workspace.as_ref())
+            .and_then(|workspace| workspace.did_change_configuration.as_ref())
+            .and_then(|did_change_configuration| did_change_configuration.dynamic_registration)
+            .unwrap_or_default();
+        if !supports_dynamic_registration {
+            return;
+        }
+
+        let registration = Registration {
+            id: "tree-sitter-lint/did-change-configuration".to_owned(),
+            method: DidChangeConfiguration::METHOD.to_owned(),
+            register_options: None,
+        };
+        if let Err(error) = self.client.register_capability(vec![registration]).await {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("couldn't register for configuration changes: {error}"),
+                )
+                .await;
+        }
+    }
+
+    fn relint_documents_under(&self, directory: &Path) {
+        self.relint_documents(|uri| {
+            uri.to_file_path()
+                .is_ok_and(|path| path.starts_with(directory))
+        });
+    }
+
+    /// Marks the matching open documents' results stale and lints them again.
+    /// Pull diagnostics clients need a `refresh_pull_diagnostics()` afterwards
+    /// to find out.
+    fn relint_documents(&self, filter: impl Fn(&Url) -> bool) {
+        let uris = self
+            .documents
+            .iter()
+            .map(|entry| entry.key().clone())
+            .filter(|uri| filter(uri))
+            .collect::<Vec<_>>();
+        for uri in uris {
+            if let Some(mut document) = self.documents.get_mut(&uri) {
+                document.result_id = None;
+            }
+            self.schedule_lint(uri, Duration::ZERO);
+        }
+    }
+
+    async fn refresh_pull_diagnostics(&self) {
+        let supports_refresh = self
+            .client_capabilities
+            .get()
+            .and_then(|capabilities| capabilities.workspace.as_ref())
+            .and_then(|workspace| workspace.diagnostic.as_ref())
+            .and_then(|diagnostic| diagnostic.refresh_support)
+            .unwrap_or_default();
+        if supports_refresh && self.uses_pull_diagnostics() {
+            let _ = self
+                .client
+                .send_request::<WorkspaceDiagnosticRefresh>(())
+                .await;
+        }
+    }
+
+    /// The local binary from `uri`'s settings (relative to its workspace
+    /// folder), or else from `config`.
+    fn local_binary_for(&self, uri: &Url, config: &Config) -> Option<Arc<LocalBinary>> {
+        let binary = match &self.settings_for(uri).local_binary {
+            Some(binary) => match self.workspace_folder_for(uri) {
+                Some(folder) => folder.join(binary),
+                None => binary.clone(),
+            },
+            None => config.local_binary.clone()?,
+        };
+        Some(
+            self.local_binaries
+                .entry(binary.clone())
+                .or_insert_with(|| Arc::new(LocalBinary::new(binary)))
+                .clone(),
+        )
+    }
+
+    /// Lints `text` with the project's local binary if one is configured,
+    /// falling back to the built-in rules if there isn't one (or it fails,
+    /// when directives aren't checked, as they're likely to be for rules
+    /// only the binary has).
+    /// Reports nothing if linting is disabled for `uri`, and applies its
+    /// severity overrides and docs URLs otherwise.
+    async fn lint_text(
+        &self,
+        uri: &Url,
+        text: String,
+        tree: Option<Tree>,
+        language: SupportedLanguage,
+        cancellation: CancellationToken,
+    ) -> Option<Vec<Violation>> {
+        let settings = self.settings_for(uri);
+        if !settings.enable {
+            return Some(vec![]);
+        }
+        let config = self.config_for(uri).await;
+        let finish = |mut violations: Vec<Violation>| {
+            config.apply_docs_urls(&mut violations);
+            settings.apply_severity_overrides(violations)
+        };
+        let mut local_binary_failed = false;
+        if let (Some(local_binary), Ok(path)) =
+            (self.local_binary_for(uri, &config), uri.to_file_path())
+        {
+            local_binary_failed = true;
+            let text = text.clone();
+            let tree = tree.clone();
+            match tokio::task::spawn_blocking(move || {
+                let mut violations = local_binary.lint(&path, &text)?;
+                let rules = local_binary.rules()?;
+                for violation in &mut violations {
+                    if violation.docs_url.is_none() {
+                        violation.docs_url = rules
+                            .iter()
+                            .find(|meta| {
+                                meta.plugin == violation.plugin && meta.name == violation.rule
+                            })
+                            .and_then(|meta| meta.docs_url.clone());
+                    }
+                }
+                let Some(tree) = tree else {
+                    return Ok::<_, LocalBinaryError>(violations);
+                };
+                Ok(lint::finish_violations(
+                    &text,
+                    &tree,
+                    language,
+                    violations,
+                    |name| has_rule(&rules, name),
+                ))
+            })
+            .await
+            {
+                Ok(Ok(violations)) => return Some(finish(violations)),
+                Ok(Err(LocalBinaryError::Unavailable { .. })) => {}
+                Ok(Err(error)) => {
+                    self.client
+                        .log_message(MessageType::ERROR, error.to_string())
+                        .await;
+                }
+                Err(_) => return None,
+            }
+        }
+
+        let tree = tree?;
+        let linter = self.linter.clone();
+        let linter_config = config.clone();
+        let mut violations = tokio::task::spawn_blocking(move || {
+            linter.lint_cancellable(&text, &tree, language, &linter_config, &cancellation)
+        })
+        .await
+        .ok()
+        .flatten()?;
+        if local_binary_failed {
+            violations.retain(|violation| !directives::is_directive_problem(violation));
+        }
+        Some(finish(violations))
+    }
+
+    async fn rules_for(&self, uri: &Url, config: &Config) -> Arc<Vec<RuleMeta>> {
+        if let Some(local_binary) = self.local_binary_for(uri, config) {
+            match tokio::task::spawn_blocking(move || local_binary.rules()).await {
+                Ok(Ok(rules)) => return rules,
+                Ok(Err(LocalBinaryError::Unavailable { .. })) => {}
+                Ok(Err(error)) => {
+                    self.client
+                        .log_message(MessageType::ERROR, error.to_string())
+                        .await;
+                }
+                Err(_) => {}
+            }
+        }
+        Arc::new(self.linter.rules())
+    }
+
+    async fn fix_all_text(
+        &self,
+        uri: &Url,
+        text: String,
+        language: SupportedLanguage,
+    ) -> Option<String> {
+        let settings = self.settings_for(uri);
+        if !settings.enable {
+            return None;
+        }
+        let config = self.config_for(uri).await;
+        if let (Some(local_binary), Ok(path)) =
+            (self.local_binary_for(uri, &config), uri.to_file_path())
+        {
+            return tokio::task::spawn_blocking(move || {
+                fix_all_with(&text, |source| {
+                    let violations = local_binary.lint(&path, source).ok()?;
+                    let rules = local_binary.rules().ok()?;
+                    let tree = lint::parse(source, language)?;
+                    Some(settings.apply_severity_overrides(directives::apply(
+                        source,
+                        &tree,
+                        language,
+                        violations,
+                        |name| has_rule(&rules, name),
+                    )))
+                })
+            })
+            .await
+            .ok()
+            .flatten();
+        }
+
+        let linter = self.linter.clone();
+        tokio::task::spawn_blocking(move || {
+            fix_all_with(&text, |source| {
+                let tree = lint::parse(source, language)?;
+                Some(
+                    settings
+                        .apply_severity_overrides(linter.lint(source, &tree, language, &config)),
+                )
+            })
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+
+    fn uses_pull_diagnostics(&self) -> bool {
+        self.client_capabilities
+            .get()
+            .and_then(|capabilities| capabilities.text_document.as_ref())
+            .is_some_and(|text_document| text_document.diagnostic.is_some())
+    }
+
+    /// Lints `uri` after `delay` and publishes its diagnostics. Clients that
+    /// pull diagnostics ask for them when they want them instead, so nothing
+    /// gets scheduled for them.
+    fn schedule_lint(&self, uri: Url, delay: Duration) {
+        if self.uses_pull_diagnostics() {
+            return;
+        }
+        let backend = self.clone();
+        self.scheduler
+            .schedule(uri.clone(), delay, move |cancellation| async move {
+                backend.lint_and_publish(uri, cancellation).await;
+            });
+    }
+
+    async fn lint_and_publish(&self, uri: Url, cancellation: CancellationToken) {
+        let Some(version) = self.lint_document(&uri, cancellation).await else {
+            return;
+        };
+        let diagnostics = {
+            let Some(document) = self.documents.get(&uri) else {
+                return;
+            };
+            if document.version != version {
+                return;
+            }
+            let encoding = self.position_encoding();
+            document
+                .violations
+                .iter()
+                .map(|violation| violation_to_diagnostic(violation, &uri, &document.rope, encoding))
+                .collect()
+        };
+
+        self.client
+            .publish_diagnostics(uri, diagnostics, Some(version))
+            .await;
+    }
+
+    /// Lints the current contents of `uri` and stores the resulting violations
+    /// on the document, returning the version they were computed for. Returns
+    /// `None` if the document changed (or `cancellation` fired) in the
+    /// meantime.
+    async fn lint_document(&self, uri: &Url, cancellation: CancellationToken) -> Option<i32> {
+        let (text, tree, language, version) = self.documents.get(uri).map(|document| {
+            (
+                document.text(),
+                document.tree.clone(),
+                document.language,
+                document.version,
+            )
+        })?;
+
+        let violations = if is_config_document(uri) {
+            self.validate_config_document(uri, &text, tree.as_ref()?)
+                .await
+        } else {
+            self.lint_text(uri, text, tree, language, cancellation.clone())
+                .await?
+        };
+
+        let mut document = self.documents.get_mut(uri)?;
+        if document.version != version || cancellation.is_cancelled() {
+            return None;
+        }
+        document.set_violations(violations);
+        Some(version)
+    }
+
+    async fn validate_config_document(&self, uri: &Url, text: &str, tree: &Tree) -> Vec<Violation> {
+        let config = self.config_for(uri).await;
+        let rules = self.rules_for(uri, &config).await;
+        config_document::validate(text, tree, &rules)
+    }
+
+    /// Works out which grammar to parse an opened document with, logging
+    /// (and returning `None`) if there isn't one or if none of the rules
+    /// apply to it.
+    async fn document_language(
+        &self,
+        uri: &Url,
+        language_id: &str,
+        text: &str,
+    ) -> Option<SupportedLanguage> {
+        if is_config_document(uri) {
+            return Some(SupportedLanguage::Yaml);
+        }
+        let config = self.config_for(uri).await;
+        let path = uri.to_file_path().ok();
+        let language = config.detect_language(Some(language_id), path.as_deref(), text);
+        let Some(language) = language else {
+            self.client
+                .log_message(
+                    MessageType::LOG,
+                    format!("skipping {uri}: unrecognized language `{language_id}`"),
+                )
+                .await;
+            return None;
+        };
+        if !self.has_rules_for(uri, &config, language).await {
+            self.client
+                .log_message(
+                    MessageType::LOG,
+                    format!("skipping {uri}: no rules apply to {language}"),
+                )
+                .await;
+            return None;
+        }
+        Some(language)
+    }
+
+    /// Like `document_language()` but for files that aren't open (going by
+    /// their path alone), and quietly skipping any not included by their
+    /// config.
+    async fn file_language(&self, uri: &Url, path: &Path) -> Option<SupportedLanguage> {
+        let config = self.config_for(uri).await;
+        let rules = self.rules_for(uri, &config).await;
+        included_file_language(path, &config, &rules)
+    }
+
+    async fn has_rules_for(&self, uri: &Url, config: &Config, language: SupportedLanguage) -> bool {
+        has_rules_for(&self.rules_for(uri, config).await, language)
+    }
+
+    /// Lints the workspace folders whose settings ask for it.
+    fn spawn_workspace_lint(&self) {
+        let roots = self
+            .workspace_folders
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|root| {
+                Url::from_directory_path(root)
+                    .is_ok_and(|uri| self.settings_for(&uri).lint_workspace)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        let lints_workspace = !roots.is_empty();
+        if !lints_workspace {
+            self.workspace_lint.lock().unwrap().cancel();
+        }
+        let backend = self.clone();
+        tokio::spawn(async move {
+            backend
+                .clear_workspace_results(|path| roots.iter().any(|root| path.starts_with(root)))
+                .await;
+            backend.watch_source_files(lints_workspace).await;
+            if lints_workspace {
+                backend.lint_workspace(roots).await;
+            }
+        });
+    }
+
+    /// Forgets the results of linting files that aren't open (apart from
+    /// those `keep` says to), publishing empty diagnostics in their place.
+    async fn clear_workspace_results(&self, keep: impl Fn(&Path) -> bool) {
+        let uris = self
+            .file_reports
+            .iter()
+            .map(|entry| entry.key().clone())
+            .filter(|uri| !uri.to_file_path().is_ok_and(|path| keep(&path)))
+            .collect::<Vec<_>>();
+        if uris.is_empty() {
+            return;
+        }
+        for uri in uris {
+            self.file_reports.remove(&uri);
+            if !self.documents.contains_key(&uri) {
+                self.client.publish_diagnostics(uri, vec![], None).await;
+            }
+        }
+        self.refresh_pull_diagnostics().await;
+    }
+
+    fn spawn_lint_workspace(&self, roots: Vec<PathBuf>) {
+        let backend = self.clone();
+        tokio::spawn(async move { backend.lint_workspace(roots).await });
+    }
+
+    /// Lints every file in `roots` that isn't open, publishing diagnostics
+    /// for each (or asking pull diagnostics clients to pull them). Cancels
+    /// whichever run was previously in progress.
+    async fn lint_workspace(&self, roots: Vec<PathBuf>) {
+        let cancellation = CancellationToken::default();
+        std::mem::replace(
+            &mut *self.workspace_lint.lock().unwrap(),
+            cancellation.clone(),
+        )
+        .cancel();
+
+        let supports_progress = self
+            .client_capabilities
+            .get()
+            .and_then(|capabilities| capabilities.window.as_ref())
+            .and_then(|window| window.work_done_progress)
+            .unwrap_or_default();
+        let progress = Arc::new(
+            WorkDoneProgressReporter::begin(
+                self.client.clone(),
+                supports_progress,
+                "Linting workspace",
+            )
+            .await,
+        );
+        let Ok(files) = tokio::task::spawn_blocking(move || {
+            roots
+                .iter()
+                .flat_map(|root| workspace::files(root))
+                .collect::<Vec<_>>()
+        })
+        .await
+        else {
+            return;
+        };
+        // Files in the same directory share their config and settings (and
+        // so rules).
+        let mut directory_rules: HashMap<PathBuf, (Arc<Config>, Arc<Vec<RuleMeta>>)> =
+            HashMap::new();
+        let mut included = vec![];
+        for path in files {
+            if cancellation.is_cancelled() {
+                break;
+            }
+            let (Ok(uri), Some(directory)) = (Url::from_file_path(&path), path.parent()) else {
+                continue;
+            };
+            let (config, rules) = match directory_rules.get(directory) {
+                Some(config_and_rules) => config_and_rules.clone(),
+                None => {
+                    let config = self.config_for(&uri).await;
+                    let rules = self.rules_for(&uri, &config).await;
+                    directory_rules.insert(directory.to_owned(), (config.clone(), rules.clone()));
+                    (config, rules)
+                }
+            };
+            if let Some(language) = included_file_language(&path, &config, &rules) {
+                included.push((uri, path, language));
+            }
+        }
+        let total = included.len();
+        let done = Arc::new(AtomicUsize::new(0));
+        let workers = Arc::new(Semaphore::new(
+            std::thread::available_parallelism().map_or(4, NonZeroUsize::get),
+        ));
+        let mut tasks = JoinSet::new();
+        for (uri, path, language) in included {
+            let Ok(permit) = workers.clone().acquire_owned().await else {
+                break;
+            };
+            if cancellation.is_cancelled() {
+                break;
+            }
+            let backend = self.clone();
+            let cancellation = cancellation.clone();
+            let progress = progress.clone();
+            let done = done.clone();
+            tasks.spawn(async move {
+                backend.lint_file(uri, path, language, cancellation).await;
+                drop(permit);
+                progress
+                    .report(done.fetch_add(1, Ordering::Relaxed) + 1, total)
+                    .await;
+            });
+        }
+        while tasks.join_next().await.is_some() {}
+        self.refresh_pull_diagnostics().await;
+
+        if let Some(progress) = Arc::into_inner(progress) {
+            let message = if cancellation.is_cancelled() {
+                "Cancelled".to_owned()
+            } else {
+                format!("Linted {total} files")
+            };
+            progress.end(Some(message)).await;
+        }
+    }
+
+    /// Lints `path` as it is on disk and publishes the results (unless the
+    /// client pulls them with `workspace_diagnostic()`), unless it's open (in
+    /// which case its document's diagnostics take precedence).
+    async fn lint_file(
+        &self,
+        uri: Url,
+        path: PathBuf,
+        language: SupportedLanguage,
+        cancellation: CancellationToken,
+    ) {
+        if self.documents.contains_key(&uri) {
+            return;
+        }
+        let Ok(Some((text, tree))) = tokio::task::spawn_blocking(move || {
+            let text = std::fs::read_to_string(path).ok()?;
+            let tree = lint::parse(&text, language);
+            Some((text, tree))
+        })
+        .await
+        else {
+            return;
+        };
+        let Some(violations) = self
+            .lint_text(&uri, text.clone(), tree, language, cancellation.clone())
+            .await
+        else {
+            return;
+        };
+        if cancellation.is_cancelled() || self.documents.contains_key(&uri) {
+            return;
+        }
+        let rope = Rope::from_str(&text);
+        let encoding = self.position_encoding();
+        let diagnostics = violations
+            .iter()
+            .map(|violation| violation_to_diagnostic(violation, &uri, &rope, encoding))
+            .collect::<Vec<_>>();
+        if !self.uses_pull_diagnostics() {
+            self.client
+                .publish_diagnostics(uri.clone(), diagnostics.clone(), None)
+                .await;
+        }
+        self.file_reports.insert(uri, FileReport::new(diagnostics));
+    }
+
+    /// Lints `path` again when it changes (if it's part of a workspace folder
+    /// being linted, and not open), or clears its results if it's gone. Pull
+    /// diagnostics clients need a `refresh_pull_diagnostics()` afterwards.
+    async fn source_file_changed(&self, uri: Url, path: PathBuf, change: FileChangeType) {
+        if !self.settings_for(&uri).lint_workspace || self.documents.contains_key(&uri) {
+            return;
+        }
+        let root = self
+            .workspace_folder_for(&uri)
+            .filter(|_| change != FileChangeType::DELETED);
+        let included = match root {
+            Some(root) => {
+                let path = path.clone();
+                tokio::task::spawn_blocking(move || workspace::includes(&root, &path))
+                    .await
+                    .unwrap_or_default()
+            }
+            None => false,
+        };
+        let language = if included {
+            self.file_language(&uri, &path).await
+        } else {
+            None
+        };
+        match language {
+            Some(language) => {
+                let cancellation = self.workspace_lint.lock().unwrap().clone();
+                self.lint_file(uri, path, language, cancellation).await;
+            }
+            None => {
+                if self.file_reports.remove(&uri).is_some() {
+                    self.client.publish_diagnostics(uri, vec![], None).await;
+                }
+            }
+        }
+    }
+
+    async fn lint_file_command(&self, uri: Url) {
+        if self.documents.contains_key(&uri) {
+            self.relint_documents(|document_uri| *document_uri == uri);
+            self.refresh_pull_diagnostics().await;
+            return;
+        }
+        let Ok(path) = uri.to_file_path() else {
+            return;
+        };
+        if let Some(language) = self.file_language(&uri, &path).await {
+            self.lint_file(uri, path, language, Default::default())
+                .await;
+            self.refresh_pull_diagnostics().await;
+        }
+    }
+
+    async fn fix_file_command(&self, uri: Url) -> Result<()> {
+        let Some((text, language, version)) = self
+            .documents
+            .get(&uri)
+            .map(|document| (document.text(), document.language, document.version))
+        else {
+            return Err(Error::invalid_params(format!("{uri} isn't open")));
+        };
+        let Some(fixed) = self.fix_all_text(&uri, text.clone(), language).await else {
+            return Ok(());
+        };
+        if self
+            .documents
+            .get(&uri)
+            .is_none_or(|document| document.version != version)
+        {
+            return Ok(());
+        }
+        match self
+            .client
+            .apply_edit(replace_document(
+                &uri,
+                &text,
+                fixed,
+                self.position_encoding(),
+            ))
+            .await
+        {
+            Ok(response) if !response.applied => {
+                self.client
+                    .log_message(
+                        MessageType::WARNING,
+                        format!(
+                            "couldn't apply fixes to {uri}: {}",
+                            response.failure_reason.unwrap_or_default()
+                        ),
+                    )
+                    .await;
+            }
+            Ok(_) => {}
+            Err(error) => {
+                self.client
+                    .log_message(
+                        MessageType::WARNING,
+                        format!("couldn't apply fixes to {uri}: {error}"),
+                    )
+                    .await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Forgets cached configs, local binaries (killing their processes) and
+    /// the results of linting the workspace, and lints everything again.
+    async fn restart(&self) {
+        self.workspace_lint.lock().unwrap().cancel();
+        self.clear_workspace_results(|_| false).await;
+        self.configs.clear();
+        self.local_binaries.clear();
+        self.relint_documents(|_| true);
+        self.refresh_pull_diagnostics().await;
+        self.spawn_workspace_lint();
+        self.client
+            .log_message(MessageType::INFO, "tree-sitter-lint-lsp restarted")
+            .await;
+    }
+
+    /// The rule whose entry in the (open) config file `uri` is at
+    /// `position`, along with the entry's key's range.
+    async fn config_rule_at(&self, uri: &Url, position: Position) -> Option<(RuleMeta, Range)> {
+        let (name, range) = {
+            let document = self.documents.get(uri)?;
+            let char_index =
+                position_to_char_index(&document.rope, position, self.position_encoding());
+            let (name, range) = config_document::rule_at(
+                &document.text(),
+                document.tree.as_ref()?,
+                document.rope.char_to_byte(char_index),
+            )?;
+            (
+                name,
+                range_to_lsp(&document.rope, &range, self.position_encoding()),
+            )
+        };
+        let config = self.config_for(uri).await;
+        let meta = self
+            .rules_for(uri, &config)
+            .await
+            .iter()
+            .find(|meta| directives::is_rule(&name, &meta.plugin, &meta.name))?
+            .clone();
+        Some((meta, range))
+    }
+
+    async fn config_hover(&self, uri: &Url, position: Position) -> Option<Hover> {
+        let (meta, range) = self.config_rule_at(uri, position).await?;
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: rule_hover_markdown(&meta.plugin, &meta.name, Some(&meta), None),
+            }),
+            range: Some(range),
+        })
+    }
+
+    /// Finds the `rule!` definitions of the rule at `position` in the config
+    /// file `uri`, searching the Rust files in the workspace.
+    async fn rule_definitions(&self, uri: &Url, position: Position) -> Vec<Location> {
+        let Some((meta, _)) = self.config_rule_at(uri, position).await else {
+            return vec![];
+        };
+        let roots = self.workspace_folders.read().unwrap().clone();
+        let encoding = self.position_encoding();
+        tokio::task::spawn_blocking(move || {
+            roots
+                .iter()
+                .flat_map(|root| workspace::files(root))
+                .filter(|path| SupportedLanguage::from_path(path) == Some(SupportedLanguage::Rust))
+                .flat_map(|path| {
+                    let Ok(text) = std::fs::read_to_string(&path) else {
+                        return vec![];
+                    };
+                    let ranges = rule_definitions::find(&text, &meta.name);
+                    let (Ok(uri), false) = (Url::from_file_path(&path), ranges.is_empty()) else {
+                        return vec![];
+                    };
+                    let rope = Rope::from_str(&text);
+                    ranges
+                        .iter()
+                        .map(|range| Location {
+                            uri: uri.clone(),
+                            range: range_to_lsp(&rope, range, encoding),
+                        })
+                        .collect()
+                })
+                .collect()
+        })
+        .await
+        .unwrap_or_default()
+    }
+
+    /// Lints `uri` first if its results are stale, eg after an edit. With
+    /// `run: onSave` edits don't count: the results of the last lint stay
+    /// (unchanged, if the client has them) until the next save. Returns
+    /// `None` if `uri` isn't an open document.
+    async fn pull_report(
+        &self,
+        uri: &Url,
+        previous_result_id: Option<&str>,
+    ) -> Option<(i32, PullReport)> {
+        let run = self.settings_for(uri).run;
+        let is_stale = {
+            let document = self.documents.get(uri)?;
+            document.result_id.is_none()
+                || (run == Run::OnType && document.violations_version != Some(document.version))
+        };
+        if is_stale {
+            self.lint_document(uri, Default::default()).await;
+        }
+        let document = self.documents.get(uri)?;
+        Some((
+            document.version,
+            pull_report(
+                uri,
+                &document,
+                previous_result_id,
+                run == Run::OnSave,
+                self.position_encoding(),
+            ),
+        ))
+    }
+}
+
+#[tower_lsp::async_trait]
+impl LanguageServer for Backend {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        if let Some(initialization_options) = params.initialization_options {
+            let _ = self.initialization_options.set(initialization_options);
+            *self.settings.write().unwrap() = self.merge_settings(&[]).await;
+        }
+        let position_encoding = PositionEncoding::negotiate(
+            params
+                .capabilities
+                .general
+                .as_ref()
+                .and_then(|general| general.position_encodings.as_deref()),
+        );
+        let _ = self.position_encoding.set(position_encoding);
+        let _ = self.client_capabilities.set(params.capabilities);
+        *self.workspace_folders.write().unwrap() = match params.workspace_folders {
+            Some(workspace_folders) => workspace_folders
+                .into_iter()
+                .filter_map(|folder| folder.uri.to_file_path().ok())
+                .collect(),
+            #[allow(deprecated)]
+            None => params
+                .root_uri
+                .and_then(|root_uri| root_uri.to_file_path().ok())
+                .into_iter()
+                .collect(),
+        };
+
+        Ok(InitializeResult {
+            server_info: Some(ServerInfo {
+                name: env!("CARGO_PKG_NAME").to_owned(),
+                version: Some(env!("CARGO_PKG_VERSION").to_owned()),
+            }),
+            capabilities: ServerCapabilities {
+                position_encoding: Some(position_encoding.kind()),
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::INCREMENTAL),
+                        save: Some(TextDocumentSyncSaveOptions::SaveOptions(SaveOptions {
+                            include_text: Some(false),
+                        })),
+                        ..Default::default()
+                    },
+                )),
+                diagnostic_provider: Some(DiagnosticServerCapabilities::Options(
+                    DiagnosticOptions {
+                        identifier: Some("tree-sitter-lint".to_owned()),
+                        inter_file_dependencies: false,
+                        workspace_diagnostics: true,
+                        ..Default::default()
+                    },
+                )),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                completion_provider: Some(CompletionOptions::default()),
+                definition_provider: Some(OneOf::Left(true)),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: commands::ALL.map(ToOwned::to_owned).to_vec(),
+                    ..Default::default()
+                }),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![
+                            CodeActionKind::QUICKFIX,
+                            SOURCE_FIX_ALL_TREE_SITTER_LINT,
+                        ]),
+                        ..Default::default()
+                    },
+                )),
+                workspace: Some(WorkspaceServerCapabilities {
+                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                        supported: Some(true),
+                        change_notifications: Some(OneOf::Left(true)),
+                    }),
+                    file_operations: None,
+                }),
+                ..Default::default()
+            },
+        })
+    }
+
+    async fn initialized(&self, _params: InitializedParams) {
+        self.client
+            .log_message(MessageType::INFO, "tree-sitter-lint-lsp initialized")
+            .await;
+        self.register_config_file_watcher().await;
+        self.register_configuration_change_notifications().await;
+        if self.supports_configuration() {
+            self.fetch_settings().await;
+            self.relint_documents(|_| true);
+        }
+        self.spawn_workspace_lint();
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        let text_document = params.text_document;
+        let Some(language) = self
+            .document_language(
+                &text_document.uri,
+                &text_document.language_id,
+                &text_document.text,
+            )
+            .await
+        else {
+            return;
+        };
+        self.file_reports.remove(&text_document.uri);
+        self.documents.insert(
+            text_document.uri.clone(),
+            Document::new(&text_document.text, text_document.version, language),
+        );
+        self.schedule_lint(text_document.uri, Duration::ZERO);
+    }
+
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        let uri = params.text_document.uri;
+        {
+            let Some(mut document) = self.documents.get_mut(&uri) else {
+                return;
+            };
+            document.apply_changes(
+                params.content_changes,
+                params.text_document.version,
+                self.position_encoding(),
+            );
+        }
+        let settings = self.settings_for(&uri);
+        if settings.run == Run::OnType {
+            self.schedule_lint(uri, settings.debounce());
+        }
+    }
+
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        let uri = params.text_document.uri;
+        if self.settings_for(&uri).run == Run::OnSave {
+            self.relint_documents(|document_uri| *document_uri == uri);
+            self.refresh_pull_diagnostics().await;
+        } else {
+            self.schedule_lint(uri, Duration::ZERO);
+        }
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        self.scheduler.cancel(&uri);
+        if self.documents.remove(&uri).is_none() {
+            return;
+        }
+        self.client
+            .publish_diagnostics(uri.clone(), vec![], None)
+            .await;
+        if let Some(config_file) = uri.to_file_path().ok().filter(|path| is_config_file(path)) {
+            self.load_config(config_file).await;
+            return;
+        }
+        if !self.settings_for(&uri).lint_workspace {
+            return;
+        }
+        let Ok(path) = uri.to_file_path() else {
+            return;
+        };
+        if let Some(language) = self.file_language(&uri, &path).await {
+            self.lint_file(uri, path, language, Default::default())
+                .await;
+            self.refresh_pull_diagnostics().await;
+        }
+    }
+
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        {
+            let mut workspace_folders = self.workspace_folders.write().unwrap();
+            for removed in params.event.removed {
+                if let Ok(path) = removed.uri.to_file_path() {
+                    workspace_folders.retain(|folder| *folder != path);
+                }
+            }
+            workspace_folders.extend(
+                params
+                    .event
+                    .added
+                    .into_iter()
+                    .filter_map(|folder| folder.uri.to_file_path().ok()),
+            );
+        }
+        if self.supports_configuration() {
+            self.fetch_settings().await;
+        }
+        self.spawn_workspace_lint();
+    }
+
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        if self.supports_configuration() {
+            self.fetch_settings().await;
+        } else {
+            let value = params
+                .settings
+                .get(settings::SECTION)
+                .unwrap_or(&params.settings);
+            *self.settings.write().unwrap() = self.merge_settings(&[value]).await;
+        }
+        self.relint_documents(|_| true);
+        self.refresh_pull_diagnostics().await;
+        self.spawn_workspace_lint();
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        let mut config_changed = false;
+        for change in params.changes {
+            let Ok(config_file) = change.uri.to_file_path() else {
+                continue;
+            };
+            if !is_config_file(&config_file) {
+                self.source_file_changed(change.uri, config_file, change.typ)
+                    .await;
+                continue;
+            }
+            config_changed = true;
+            if change.typ == FileChangeType::DELETED {
+                self.configs.remove(&config_file);
+                self.client
+                    .publish_diagnostics(change.uri, vec![], None)
+                    .await;
+            } else {
+                self.load_config(config_file.clone()).await;
+            }
+            if let Some(directory) = config_file.parent() {
+                self.relint_documents_under(directory);
+            }
+        }
+        self.refresh_pull_diagnostics().await;
+        if config_changed {
+            self.spawn_workspace_lint();
+        }
+    }
+
+    async fn diagnostic(
+        &self,
+        params: DocumentDiagnosticParams,
+    ) -> Result<DocumentDiagnosticReportResult> {
+        let report = match self
+            .pull_report(
+                &params.text_document.uri,
+                params.previous_result_id.as_deref(),
+            )
+            .await
+        {
+            Some((_, PullReport::Unchanged(report))) => {
+                DocumentDiagnosticReport::Unchanged(RelatedUnchangedDocumentDiagnosticReport {
+                    related_documents: None,
+                    unchanged_document_diagnostic_report: report,
+                })
+            }
+            Some((_, PullReport::Full(report))) => {
+                DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
+                    related_documents: None,
+                    full_document_diagnostic_report: report,
+                })
+            }
+            None => DocumentDiagnosticReport::Full(Default::default()),
+        };
+        Ok(report.into())
+    }
+
+    async fn workspace_diagnostic(
+        &self,
+        params: WorkspaceDiagnosticParams,
+    ) -> Result<WorkspaceDiagnosticReportResult> {
+        let previous_result_ids = params
+            .previous_result_ids
+            .into_iter()
+            .map(|previous_result_id| (previous_result_id.uri, previous_result_id.value))
+            .collect::<HashMap<_, _>>();
+        let uris = self
+            .documents
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect::<Vec<_>>();
+
+        let mut reports = vec![];
+        for uri in uris {
+            if let Some((version, report)) = self
+                .pull_report(&uri, previous_result_ids.get(&uri).map(String::as_str))
+                .await
+            {
+                reports.push((uri, Some(version.into()), report));
+            }
+        }
+        reports.extend(
+            self.file_reports
+                .iter()
+                .filter(|entry| !self.documents.contains_key(entry.key()))
+                .map(|entry| {
+                    let previous_result_id = previous_result_ids.get(entry.key());
+                    (
+                        entry.key().clone(),
+                        None,
+                        entry.pull_report(previous_result_id.map(String::as_str)),
+                    )
+                }),
+        );
+
+        let mut items = vec![];
+        for (uri, version, report) in reports {
+            items.push(match report {
+                PullReport::Unchanged(report) => WorkspaceDocumentDiagnosticReport::Unchanged(
+                    WorkspaceUnchangedDocumentDiagnosticReport {
+                        uri,
+                        version,
+                        unchanged_document_diagnostic_report: report,
+                    },
+                ),
+                PullReport::Full(report) => {
+                    WorkspaceDocumentDiagnosticReport::Full(WorkspaceFullDocumentDiagnosticReport {
+                        uri,
+                        version,
+                        full_document_diagnostic_report: report,
+                    })
+                }
+            });
+        }
+        Ok(WorkspaceDiagnosticReport { items }.into())
+    }
+
+    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<Value>> {
+        match Command::parse(params)? {
+            Command::LintFile(uri) => self.lint_file_command(uri).await,
+            Command::FixFile(uri) => self.fix_file_command(uri).await?,
+            Command::LintWorkspace => {
+                let roots = self.workspace_folders.read().unwrap().clone();
+                self.spawn_lint_workspace(roots);
+            }
+            Command::Restart => self.restart().await,
+        }
+        Ok(None)
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        if is_config_document(&uri) {
+            return Ok(self.config_hover(&uri, position).await);
+        }
+        let Some(hovered) = self.documents.get(&uri).map(|document| {
+            document
+                .current_violations()
+                .iter()
+                .filter_map(|violation| {
+                    let range =
+                        range_to_lsp(&document.rope, &violation.range, self.position_encoding());
+                    (range.start <= position && position <= range.end)
+                        .then(|| (violation.plugin.clone(), violation.rule.clone(), range))
+                })
+                .collect::<Vec<_>>()
+        }) else {
+            return Ok(None);
+        };
+        if hovered.is_empty() {
+            return Ok(None);
+        }
+
+        let config = self.config_for(&uri).await;
+        let rules = self.rules_for(&uri, &config).await;
+        let markdown = hovered
+            .iter()
+            .map(|(plugin, rule, _)| {
+                rule_hover_markdown(
+                    plugin,
+                    rule,
+                    rules
+                        .iter()
+                        .find(|meta| meta.plugin == *plugin && meta.name == *rule),
+                    config.rule_options(plugin, rule),
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n---\n\n");
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: markdown,
+            }),
+            range: hovered.first().map(|&(_, _, range)| range),
+        }))
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let uri = params.text_document_position.text_document.uri;
+        if !is_config_document(&uri) {
+            return Ok(None);
+        }
+        let Some(rope) = self
+            .documents
+            .get(&uri)
+            .map(|document| document.rope.clone())
+        else {
+            return Ok(None);
+        };
+        let config = self.config_for(&uri).await;
+        let rules = self.rules_for(&uri, &config).await;
+        Ok(Some(CompletionResponse::Array(
+            config_document::completions(
+                &rope,
+                params.text_document_position.position,
+                self.position_encoding(),
+                &rules,
+            ),
+        )))
+    }
+
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        if !is_config_document(&uri) {
+            return Ok(None);
+        }
+        let locations = self
+            .rule_definitions(&uri, params.text_document_position_params.position)
+            .await;
+        Ok((!locations.is_empty()).then_some(GotoDefinitionResponse::Array(locations)))
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let uri = params.text_document.uri;
+        let only = params.context.only.as_deref();
+        let encoding = self.position_encoding();
+        let mut actions = vec![];
+        let Some((text, language)) = self.documents.get(&uri).map(|document| {
+            if is_requested(only, &CodeActionKind::QUICKFIX) {
+                actions.extend(quick_fixes(&uri, &document, &params.range, encoding));
+                actions.extend(disable_rule_actions(
+                    &uri,
+                    &document,
+                    &params.range,
+                    encoding,
+                ));
+            }
+            (document.text(), document.language)
+        }) else {
+            return Ok(None);
+        };
+        if is_requested(only, &SOURCE_FIX_ALL_TREE_SITTER_LINT) {
+            if let Some(fixed) = self.fix_all_text(&uri, text.clone(), language).await {
+                actions.push(fix_all(&uri, &text, fixed, encoding));
+            }
+        }
+        Ok(Some(actions))
+    }
+}
+
+fn has_rules_for(rules: &[RuleMeta], language: SupportedLanguage) -> bool {
+    let languages = injections::reachable_languages(language);
+    rules
+        .iter()
+        .any(|meta| languages.iter().any(|&language| meta.applies_to(language)))
+}
+
+/// The language to lint `path` (which isn't open) as, going by its path
+/// alone, unless its config doesn't include it or none of `rules` apply.
+fn included_file_language(
+    path: &Path,
+    config: &Config,
+    rules: &[RuleMeta],
+) -> Option<SupportedLanguage> {
+    if !config.includes(path) {
+        return None;
+    }
+    let language = config.detect_language(None, Some(path), "")?;
+    has_rules_for(rules, language).then_some(language)
+}
+
+fn has_rule(rules: &[RuleMeta], name: &str) -> bool {
+    rules
+        .iter()
+        .any(|meta| directives::is_rule(name, &meta.plugin, &meta.name))
+}
+
+fn is_config_document(uri: &Url) -> bool {
+    uri.to_file_path().is_ok_and(|path| is_config_file(&path))
+}
@@ -0,0 +1,327 @@
+use std::collections::{HashMap, HashSet};
+
+use ropey::Rope;
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Diagnostic, Position, Range, TextEdit, Url,
+    WorkspaceEdit,
+};
+
+use crate::{
+    diagnostics::violation_to_diagnostic,
+    directives::{self, Directive, DirectiveKind},
+    document::Document,
+    injections,
+    position::{
+        byte_to_position, document_end_position, range_to_lsp, ranges_overlap, PositionEncoding,
+    },
+    rule_queries::INVALID_RULE_QUERY,
+    violation::Edit,
+};
+
+pub const SOURCE_FIX_ALL_TREE_SITTER_LINT: CodeActionKind =
+    CodeActionKind::new("source.fixAll.tree-sitter-lint");
+
+pub fn is_requested(only: Option<&[CodeActionKind]>, kind: &CodeActionKind) -> bool {
+    let Some(only) = only else {
+        return true;
+    };
+    only.iter().any(|requested| {
+        kind.as_str() == requested.as_str()
+            || kind
+                .as_str()
+                .strip_prefix(requested.as_str())
+                .is_some_and(|rest| rest.starts_with('.'))
+    })
+}
+
+pub fn quick_fixes(
+    uri: &Url,
+    document: &Document,
+    range: &Range,
+    encoding: PositionEncoding,
+) -> Vec<CodeActionOrCommand> {
+    document
+        .current_violations()
+        .iter()
+        .filter_map(|violation| {
+            let fix = violation.fix.as_ref()?;
+            let diagnostic = violation_to_diagnostic(violation, uri, &document.rope, encoding);
+            if !ranges_overlap(&diagnostic.range, range) {
+                return None;
+            }
+            Some(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Fix this {} problem", violation.rule),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diagnostic]),
+                edit: Some(workspace_edit(uri, fix, &document.rope, encoding)),
+                is_preferred: Some(true),
+                ..Default::default()
+            }))
+        })
+        .collect()
+}
+
+/// Offers to disable each rule reported in `range`, either for the line in
+/// question or for the whole file, adding to an existing directive where
+/// there is one.
+pub fn disable_rule_actions(
+    uri: &Url,
+    document: &Document,
+    range: &Range,
+    encoding: PositionEncoding,
+) -> Vec<CodeActionOrCommand> {
+    let text = document.text();
+    let directives = document.tree.as_ref().map_or_else(Vec::new, |tree| {
+        directives::parse(&text, tree, document.language)
+    });
+    let mut seen = HashSet::new();
+    let mut actions = vec![];
+    for violation in document.current_violations() {
+        if violation.plugin == directives::PLUGIN && violation.rule != INVALID_RULE_QUERY {
+            continue;
+        }
+        let diagnostic = violation_to_diagnostic(violation, uri, &document.rope, encoding);
+        if !ranges_overlap(&diagnostic.range, range) {
+            continue;
+        }
+        let line = violation.range.start_point.row;
+        if !seen.insert((violation.plugin.as_str(), violation.rule.as_str(), line)) {
+            continue;
+        }
+        let rule = format!("{}/{}", violation.plugin, violation.rule);
+
+        let next_line_directive = directives.iter().find(|directive| {
+            directive.kind == DirectiveKind::NextLine && directive.line + 1 == line
+        });
+        let edit = match next_line_directive {
+            Some(directive) => append_rule(document, directive, &rule, encoding),
+            None => {
+                let language = document.tree.as_ref().map_or(document.language, |tree| {
+                    injections::language_at(
+                        &text,
+                        tree,
+                        document.language,
+                        violation.range.start_byte,
+                    )
+                });
+                // The row's start is always an LSP line's (but not the other
+                // way around, as LSP lines end at a lone `\r` too).
+                let row_start = violation.range.start_byte - violation.range.start_point.column;
+                let indentation = text[row_start..]
+                    .chars()
+                    .take_while(|&c| c == ' ' || c == '\t')
+                    .collect::<String>();
+                insert_line(
+                    byte_to_position(&document.rope, row_start, encoding).line as usize,
+                    format!(
+                        "{indentation}{}",
+                        language
+                            .comment_syntax()
+                            .directive(DirectiveKind::NextLine, &rule)
+                    ),
+                )
+            }
+        };
+        actions.push(disable_rule_action(
+            uri,
+            format!("Disable {rule} for this line"),
+            diagnostic.clone(),
+            edit,
+        ));
+
+        if !seen.insert((
+            violation.plugin.as_str(),
+            violation.rule.as_str(),
+            usize::MAX,
+        )) {
+            continue;
+        }
+        let file_directive = directives
+            .iter()
+            .find(|directive| directive.kind == DirectiveKind::File);
+        let edit = match file_directive {
+            Some(directive) => append_rule(document, directive, &rule, encoding),
+            None => insert_file_directive(
+                &text,
+                document
+                    .language
+                    .comment_syntax()
+                    .directive(DirectiveKind::File, &rule),
+                encoding,
+            ),
+        };
+        actions.push(disable_rule_action(
+            uri,
+            format!("Disable {rule} for the entire file"),
+            diagnostic.clone(),
+            edit,
+        ));
+    }
+    actions
+}
+
+fn disable_rule_action(
+    uri: &Url,
+    title: String,
+    diagnostic: Diagnostic,
+    edit: TextEdit,
+) -> CodeActionOrCommand {
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title,
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diagnostic]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+fn insert_line(line: usize, text: String) -> TextEdit {
+    let position = Position {
+        line: line as u32,
+        character: 0,
+    };
+    TextEdit {
+        range: Range {
+            start: position,
+            end: position,
+        },
+        new_text: format!("{text}\n"),
+    }
+}
+
+/// Inserts `directive` at the top of `text`, but after any shebang line
+/// (which has to stay first).
+fn insert_file_directive(text: &str, directive: String, encoding: PositionEncoding) -> TextEdit {
+    let has_shebang = text
+        .strip_prefix("#!")
+        .is_some_and(|rest| !rest.trim_start().starts_with('['));
+    if !has_shebang {
+        return insert_line(0, directive);
+    }
+    if text.contains('\n') {
+        return insert_line(1, directive);
+    }
+    let end = document_end_position(text, encoding);
+    TextEdit {
+        range: Range { start: end, end },
+        new_text: format!("\n{directive}"),
+    }
+}
+
+fn append_rule(
+    document: &Document,
+    directive: &Directive,
+    rule: &str,
+    encoding: PositionEncoding,
+) -> TextEdit {
+    let position = byte_to_position(&document.rope, directive.rules_end, encoding);
+    TextEdit {
+        range: Range {
+            start: position,
+            end: position,
+        },
+        new_text: format!(", {rule}"),
+    }
+}
+
+pub fn fix_all(
+    uri: &Url,
+    text: &str,
+    fixed: String,
+    encoding: PositionEncoding,
+) -> CodeActionOrCommand {
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title: "Fix all auto-fixable problems".to_owned(),
+        kind: Some(SOURCE_FIX_ALL_TREE_SITTER_LINT),
+        edit: Some(replace_document(uri, text, fixed, encoding)),
+        ..Default::default()
+    })
+}
+
+/// Replaces the whole of `text` (the document's current contents) with
+/// `replacement`.
+pub fn replace_document(
+    uri: &Url,
+    text: &str,
+    replacement: String,
+    encoding: PositionEncoding,
+) -> WorkspaceEdit {
+    WorkspaceEdit {
+        changes: Some(HashMap::from([(
+            uri.clone(),
+            vec![TextEdit {
+                range: Range {
+                    start: Default::default(),
+                    end: document_end_position(text, encoding),
+                },
+                new_text: replacement,
+            }],
+        )])),
+        ..Default::default()
+    }
+}
+
+fn workspace_edit(
+    uri: &Url,
+    edits: &[Edit],
+    rope: &Rope,
+    encoding: PositionEncoding,
+) -> WorkspaceEdit {
+    WorkspaceEdit {
+        changes: Some(HashMap::from([(
+            uri.clone(),
+            edits
+                .iter()
+                .map(|edit| edit_to_text_edit(edit, rope, encoding))
+                .collect(),
+        )])),
+        ..Default::default()
+    }
+}
+
+fn edit_to_text_edit(edit: &Edit, rope: &Rope, encoding: PositionEncoding) -> TextEdit {
+    TextEdit {
+        range: range_to_lsp(rope, &edit.range, encoding),
+        new_text: edit.replacement.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insertion(text: &str) -> (u32, u32, String) {
+        let edit = insert_file_directive(
+            text,
+            "# tree-sitter-lint-disable x/y".to_owned(),
+            PositionEncoding::Utf16,
+        );
+        assert_eq!(edit.range.start, edit.range.end);
+        (
+            edit.range.start.line,
+            edit.range.start.character,
+            edit.new_text,
+        )
+    }
+
+    #[test]
+    fn test_file_directive_goes_after_shebang() {
+        let directive = "# tree-sitter-lint-disable x/y";
+        assert_eq!(insertion("x = 1\n"), (0, 0, format!("{directive}\n")));
+        assert_eq!(
+            insertion("#!/usr/bin/env python3\nx = 1\n"),
+            (1, 0, format!("{directive}\n"))
+        );
+        assert_eq!(
+            insertion("#!/usr/bin/env python3"),
+            (0, 22, format!("\n{directive}"))
+        );
+        assert_eq!(
+            insertion("#![allow(dead_code)]\n"),
+            (0, 0, format!("{directive}\n"))
+        );
+    }
+}
@@ -0,0 +1,46 @@
+use serde_json::Value;
+use tower_lsp::{
+    jsonrpc::{Error, Result},
+    lsp_types::{ExecuteCommandParams, Url},
+};
+
+pub const LINT_FILE: &str = "tree-sitter-lint.lintFile";
+pub const FIX_FILE: &str = "tree-sitter-lint.fixFile";
+pub const LINT_WORKSPACE: &str = "tree-sitter-lint.lintWorkspace";
+pub const RESTART: &str = "tree-sitter-lint.restart";
+
+pub const ALL: [&str; 4] = [LINT_FILE, FIX_FILE, LINT_WORKSPACE, RESTART];
+
+pub enum Command {
+    LintFile(Url),
+    FixFile(Url),
+    LintWorkspace,
+    Restart,
+}
+
+impl Command {
+    pub fn parse(params: ExecuteCommandParams) -> Result<Self> {
+        match &*params.command {
+            LINT_FILE => Ok(Self::LintFile(uri_argument(params.arguments)?)),
+            FIX_FILE => Ok(Self::FixFile(uri_argument(params.arguments)?)),
+            LINT_WORKSPACE => Ok(Self::LintWorkspace),
+            RESTART => Ok(Self::Restart),
+            command => Err(Error::invalid_params(format!("unknown command: {command}"))),
+        }
+    }
+}
+
+/// Accepts either a bare URI or a `TextDocumentIdentifier`-shaped object as
+/// the first argument.
+fn uri_argument(arguments: Vec<Value>) -> Result<Url> {
+    let argument = arguments
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::invalid_params("expected a document URI argument"))?;
+    let argument = match argument {
+        Value::Object(mut object) => object.remove("uri").unwrap_or_default(),
+        argument => argument,
+    };
+    serde_json::from_value(argument)
+        .map_err(|error| Error::invalid_params(format!("invalid document URI: {error}")))
+}
@@ -0,0 +1,326 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use globset::{Glob, GlobMatcher, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::{
+    language::SupportedLanguage,
+    rule::Rule,
+    violation::{RuleLevel, Violation},
+};
+
+pub const CONFIG_FILE_NAME: &str = ".tree-sitter-lint.yml";
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("couldn't read {CONFIG_FILE_NAME}: {0}")]
+    Io(#[from] io::Error),
+    #[error("couldn't parse {CONFIG_FILE_NAME}: {0}")]
+    Parse(#[from] serde_yaml::Error),
+    #[error("invalid glob in {CONFIG_FILE_NAME}: {0}")]
+    Glob(#[from] globset::Error),
+}
+
+impl ConfigError {
+    /// The zero-based line and column the error points at, if known.
+    pub fn location(&self) -> Option<(usize, usize)> {
+        match self {
+            Self::Io(_) | Self::Glob(_) => None,
+            Self::Parse(error) => error
+                .location()
+                .map(|location| (location.line() - 1, location.column() - 1)),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub rules: HashMap<String, RuleConfig>,
+    /// Path to the project's `tree-sitter-lint-local` binary, relative to the
+    /// config file.
+    pub local_binary: Option<PathBuf>,
+    /// Globs (relative to the config file) selecting which files get linted
+    /// when linting the whole workspace. Everything is included by default.
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Globs (relative to the config file) mapped to the language matching
+    /// files should be parsed as, overriding what would otherwise be
+    /// detected. The first matching glob wins.
+    #[serde(default, deserialize_with = "deserialize_ordered_map")]
+    pub languages: Vec<(String, SupportedLanguage)>,
+    /// URL templates for each plugin's rule docs, eg
+    /// `https://example.com/{plugin}/{rule}`, linked from diagnostics in
+    /// preference to the URLs rules declare themselves.
+    #[serde(default)]
+    pub docs_urls: HashMap<String, String>,
+    #[serde(skip)]
+    root: Option<PathBuf>,
+    #[serde(skip)]
+    file_filter: FileFilter,
+    #[serde(skip)]
+    language_overrides: Vec<(GlobMatcher, SupportedLanguage)>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let mut config = Self::parse(&fs::read_to_string(path)?)?;
+        if let (Some(local_binary), Some(directory)) = (config.local_binary.as_mut(), path.parent())
+        {
+            *local_binary = directory.join(&*local_binary);
+        }
+        config.root = path.parent().map(Path::to_owned);
+        Ok(config)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let mut config = serde_yaml::from_str::<Option<Self>>(text)?.unwrap_or_default();
+        config.file_filter = FileFilter::new(&config.include, &config.exclude)?;
+        config.language_overrides = config
+            .languages
+            .iter()
+            .map(|(glob, language)| Ok((Glob::new(glob)?.compile_matcher(), *language)))
+            .collect::<Result<_, globset::Error>>()?;
+        Ok(config)
+    }
+
+    /// Whether `path` matches the config's `include`/`exclude` globs.
+    pub fn includes(&self, path: &Path) -> bool {
+        self.file_filter.matches(self.relative_path(path))
+    }
+
+    pub fn language_override(&self, path: &Path) -> Option<SupportedLanguage> {
+        let path = self.relative_path(path);
+        self.language_overrides
+            .iter()
+            .find(|(glob, _)| glob.is_match(path))
+            .map(|&(_, language)| language)
+    }
+
+    /// The language of a document: as overridden by `languages`, or else as
+    /// detected from its `languageId`, path or shebang.
+    pub fn detect_language(
+        &self,
+        language_id: Option<&str>,
+        path: Option<&Path>,
+        text: &str,
+    ) -> Option<SupportedLanguage> {
+        path.and_then(|path| self.language_override(path))
+            .or_else(|| SupportedLanguage::detect(language_id, path, text))
+    }
+
+    fn relative_path<'a>(&self, path: &'a Path) -> &'a Path {
+        match &self.root {
+            Some(root) => path.strip_prefix(root).unwrap_or(path),
+            None => path,
+        }
+    }
+
+    pub fn rule_options(&self, plugin: &str, rule: &str) -> Option<&Value> {
+        self.rules
+            .get(&format!("{plugin}/{rule}"))
+            .map(|rule_config| &rule_config.options)
+            .filter(|options| !options.is_null())
+    }
+
+    pub fn docs_url(&self, plugin: &str, rule: &str) -> Option<String> {
+        self.docs_urls
+            .get(plugin)
+            .map(|template| template.replace("{plugin}", plugin).replace("{rule}", rule))
+    }
+
+    /// Points `violations` at the docs URLs from `docs_urls`, where there
+    /// are any.
+    pub fn apply_docs_urls(&self, violations: &mut [Violation]) {
+        if self.docs_urls.is_empty() {
+            return;
+        }
+        for violation in violations {
+            if let Some(docs_url) = self.docs_url(&violation.plugin, &violation.rule) {
+                violation.docs_url = Some(docs_url);
+            }
+        }
+    }
+
+    /// Returns the level and options `rule` should run with, or `None` if
+    /// it's been turned off.
+    pub fn resolve_rule(&self, plugin: &str, rule: &Rule) -> Option<(RuleLevel, &Value)> {
+        let Some(rule_config) = self.rules.get(&format!("{plugin}/{}", rule.name)) else {
+            return Some((rule.level, &Value::Null));
+        };
+        let level = match rule_config.level {
+            None => rule.level,
+            Some(level) => level.rule_level()?,
+        };
+        Some((level, &rule_config.options))
+    }
+}
+
+#[derive(Debug, Default)]
+struct FileFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl FileFilter {
+    fn new(include: &[String], exclude: &[String]) -> Result<Self, globset::Error> {
+        Ok(Self {
+            include: (!include.is_empty())
+                .then(|| build_glob_set(include))
+                .transpose()?,
+            exclude: build_glob_set(exclude)?,
+        })
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        self.include
+            .as_ref()
+            .is_none_or(|include| include.is_match(path))
+            && !self.exclude.is_match(path)
+    }
+}
+
+fn build_glob_set(globs: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob)?);
+    }
+    builder.build()
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfiguredLevel {
+    Off,
+    Hint,
+    #[serde(alias = "info")]
+    Information,
+    #[serde(alias = "warn")]
+    Warning,
+    Error,
+}
+
+impl ConfiguredLevel {
+    pub const NAMES: [&'static str; 5] = ["off", "hint", "information", "warning", "error"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        serde_json::from_value(Value::String(name.to_owned())).ok()
+    }
+
+    /// `None` if the rule's turned off.
+    pub fn rule_level(self) -> Option<RuleLevel> {
+        match self {
+            Self::Off => None,
+            Self::Hint => Some(RuleLevel::Hint),
+            Self::Information => Some(RuleLevel::Information),
+            Self::Warning => Some(RuleLevel::Warning),
+            Self::Error => Some(RuleLevel::Error),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(from = "RuleConfigRepr")]
+pub struct RuleConfig {
+    pub level: Option<ConfiguredLevel>,
+    pub options: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RuleConfigRepr {
+    Level(ConfiguredLevel),
+    Full {
+        level: Option<ConfiguredLevel>,
+        #[serde(default)]
+        options: Value,
+    },
+}
+
+impl From<RuleConfigRepr> for RuleConfig {
+    fn from(repr: RuleConfigRepr) -> Self {
+        match repr {
+            RuleConfigRepr::Level(level) => Self {
+                level: Some(level),
+                options: Value::Null,
+            },
+            RuleConfigRepr::Full { level, options } => Self { level, options },
+        }
+    }
+}
+
+/// Deserializes a map, keeping its entries in the order they were written.
+fn deserialize_ordered_map<'de, D, V>(deserializer: D) -> Result<Vec<(String, V)>, D::Error>
+where
+    D: Deserializer<'de>,
+    V: serde::de::DeserializeOwned,
+{
+    serde_yaml::Mapping::deserialize(deserializer)?
+        .into_iter()
+        .map(|(key, value)| {
+            Ok((
+                serde_yaml::from_value(key).map_err(serde::de::Error::custom)?,
+                serde_yaml::from_value(value).map_err(serde::de::Error::custom)?,
+            ))
+        })
+        .collect()
+}
+
+pub fn is_config_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|file_name| file_name == CONFIG_FILE_NAME)
+}
+
+/// Looks for the nearest config file to `path`, walking up no further than
+/// the workspace folder that contains it.
+pub fn find_config_file(path: &Path, workspace_folders: &[PathBuf]) -> Option<PathBuf> {
+    let workspace_folder = workspace_folders
+        .iter()
+        .filter(|folder| path.starts_with(folder))
+        .max_by_key(|folder| folder.components().count());
+    for directory in path.ancestors().skip(1) {
+        let candidate = directory.join(CONFIG_FILE_NAME);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        if workspace_folder.is_some_and(|folder| folder == directory) {
+            break;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_override_takes_precedence() {
+        let config =
+            Config::parse("languages:\n  \"*.txt\": python\n  \"**/bin/*\": rust\n").unwrap();
+        assert_eq!(
+            config.detect_language(
+                Some("javascript"),
+                Some(Path::new("/p/bin/tool")),
+                "#!/usr/bin/env node\n"
+            ),
+            Some(SupportedLanguage::Rust)
+        );
+        assert_eq!(
+            config.detect_language(None, Some(Path::new("notes.txt")), ""),
+            Some(SupportedLanguage::Python)
+        );
+        assert_eq!(
+            config.detect_language(Some("javascript"), Some(Path::new("/p/a.py")), ""),
+            Some(SupportedLanguage::Javascript)
+        );
+    }
+}
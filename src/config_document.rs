@@ -0,0 +1,575 @@
+//! Language support for the config file itself: validating it against the
+//! rules that are available, and completing rule names, levels and options.
+
+use ropey::Rope;
+use serde_json::Value;
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionTextEdit, Documentation, MarkupContent,
+    MarkupKind, Position, Range, TextEdit,
+};
+use tree_sitter::{Node, Tree};
+
+use crate::{
+    config::{Config, ConfiguredLevel},
+    directives::is_rule,
+    position::{byte_to_position, position_to_char_index, PositionEncoding},
+    rule::RuleMeta,
+    schema,
+    violation::{RuleLevel, Violation},
+};
+
+pub const INVALID_CONFIG: &str = "invalid-config";
+pub const UNKNOWN_RULE: &str = "unknown-rule";
+pub const UNKNOWN_KEY: &str = "unknown-key";
+pub const INVALID_LEVEL: &str = "invalid-level";
+pub const INVALID_OPTIONS: &str = "invalid-options";
+
+const TOP_LEVEL_KEYS: [(&str, &str); 6] = [
+    ("rules", "Levels and options for each rule"),
+    ("include", "Globs selecting which files get linted"),
+    ("exclude", "Globs selecting which files don't get linted"),
+    (
+        "languages",
+        "Globs mapped to the language matching files are parsed as",
+    ),
+    (
+        "local_binary",
+        "Path to the project's `tree-sitter-lint-local` binary",
+    ),
+    ("docs_urls", "URL templates for each plugin's rule docs"),
+];
+
+const RULE_CONFIG_KEYS: [(&str, &str); 2] = [
+    ("level", "The level to report the rule's problems at"),
+    ("options", "Options passed to the rule"),
+];
+
+/// Checks the `rules` in a config file: that each is known, has a valid
+/// level and that its options match its schema. Anything else wrong with
+/// the file is reported as a single problem, if those checks pass.
+pub fn validate(source: &str, tree: &Tree, rules: &[RuleMeta]) -> Vec<Violation> {
+    let mut problems = vec![];
+    for (name, key, value) in rule_configs(source, tree) {
+        let Some(meta) = rules
+            .iter()
+            .find(|meta| is_rule(&name, &meta.plugin, &meta.name))
+        else {
+            problems.push(Violation::internal(
+                source,
+                UNKNOWN_RULE,
+                RuleLevel::Warning,
+                format!("Unknown rule `{name}`."),
+                key.byte_range(),
+            ));
+            continue;
+        };
+        let Some(value) = value else {
+            continue;
+        };
+        if !is_mapping(value) {
+            check_level(source, value, &mut problems);
+            continue;
+        }
+        for (key_name, key, value) in pairs(source, value) {
+            match (key_name.as_str(), value) {
+                ("level", Some(value)) => check_level(source, value, &mut problems),
+                ("options", Some(value)) => check_options(source, meta, value, &mut problems),
+                ("level" | "options", None) => {}
+                (key_name, _) => problems.push(Violation::internal(
+                    source,
+                    UNKNOWN_KEY,
+                    RuleLevel::Warning,
+                    format!("Unknown key `{key_name}`. Expected `level` or `options`."),
+                    key.byte_range(),
+                )),
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        if let Err(error) = Config::parse(source) {
+            let (row, column) = error.location().unwrap_or_default();
+            let line = source.split_inclusive('\n').nth(row).unwrap_or_default();
+            let byte = source
+                .split_inclusive('\n')
+                .take(row)
+                .map(str::len)
+                .sum::<usize>()
+                + line.chars().take(column).map(char::len_utf8).sum::<usize>();
+            problems.push(Violation::internal(
+                source,
+                INVALID_CONFIG,
+                RuleLevel::Error,
+                error.to_string(),
+                byte..byte,
+            ));
+        }
+    }
+    problems.sort_by_key(|problem| problem.range.start_byte);
+    problems
+}
+
+/// The rule configured by the entry of `rules` whose key contains `byte`,
+/// along with the key's range.
+pub fn rule_at(source: &str, tree: &Tree, byte: usize) -> Option<(String, tree_sitter::Range)> {
+    rule_configs(source, tree)
+        .into_iter()
+        .find(|(_, key, _)| key.start_byte() <= byte && byte <= key.end_byte())
+        .map(|(name, key, _)| (name, key.range()))
+}
+
+fn check_level(source: &str, value: Node, problems: &mut Vec<Violation>) {
+    if yaml_value(source, value)
+        .as_str()
+        .and_then(ConfiguredLevel::from_name)
+        .is_some()
+    {
+        return;
+    }
+    problems.push(Violation::internal(
+        source,
+        INVALID_LEVEL,
+        RuleLevel::Error,
+        format!(
+            "Invalid level `{}`. Expected one of {}.",
+            &source[value.byte_range()],
+            ConfiguredLevel::NAMES.join(", ")
+        ),
+        value.byte_range(),
+    ));
+}
+
+fn check_options(source: &str, meta: &RuleMeta, options: Node, problems: &mut Vec<Violation>) {
+    let Some(options_schema) = &meta.options_schema else {
+        return;
+    };
+    for error in schema::validate(options_schema, &yaml_value(source, options)) {
+        problems.push(Violation::internal(
+            source,
+            INVALID_OPTIONS,
+            RuleLevel::Error,
+            error.message,
+            node_at_path(source, options, &error.path)
+                .unwrap_or(options)
+                .byte_range(),
+        ));
+    }
+}
+
+/// The entries of the top-level `rules` mapping.
+fn rule_configs<'tree>(
+    source: &str,
+    tree: &'tree Tree,
+) -> Vec<(String, Node<'tree>, Option<Node<'tree>>)> {
+    top_level_mapping(tree)
+        .and_then(|mapping| {
+            pairs(source, mapping)
+                .into_iter()
+                .find(|(key, _, _)| key == "rules")
+        })
+        .and_then(|(_, _, value)| value)
+        .map(|rule_configs| pairs(source, rule_configs))
+        .unwrap_or_default()
+}
+
+fn top_level_mapping(tree: &Tree) -> Option<Node<'_>> {
+    let root = tree.root_node();
+    let document = root
+        .named_children(&mut root.walk())
+        .find(|child| child.kind() == "document")?;
+    let node = document
+        .named_children(&mut document.walk())
+        .find(|child| matches!(child.kind(), "block_node" | "flow_node"))?;
+    Some(unwrap(node)).filter(|node| is_mapping(*node))
+}
+
+/// Skips past the wrappers (and any anchor or tag) around a YAML value.
+fn unwrap(mut node: Node) -> Node {
+    while matches!(node.kind(), "block_node" | "flow_node") {
+        let Some(child) = node
+            .named_children(&mut node.walk())
+            .find(|child| !matches!(child.kind(), "anchor" | "tag"))
+        else {
+            break;
+        };
+        node = child;
+    }
+    node
+}
+
+fn is_mapping(node: Node) -> bool {
+    matches!(unwrap(node).kind(), "block_mapping" | "flow_mapping")
+}
+
+/// The entries of `mapping` (if it is one): each key, the node for it and
+/// the node for its value, if it has one.
+fn pairs<'tree>(
+    source: &str,
+    mapping: Node<'tree>,
+) -> Vec<(String, Node<'tree>, Option<Node<'tree>>)> {
+    let mapping = unwrap(mapping);
+    let children = match mapping.kind() {
+        "block_mapping" | "flow_mapping" => mapping.named_children(&mut mapping.walk()).collect(),
+        _ => vec![],
+    };
+    children
+        .into_iter()
+        .filter_map(|pair| {
+            let key = pair.child_by_field_name("key")?;
+            let name = match yaml_value(source, key) {
+                Value::String(name) => name,
+                _ => source[key.byte_range()].to_owned(),
+            };
+            Some((name, key, pair.child_by_field_name("value").map(unwrap)))
+        })
+        .collect()
+}
+
+fn sequence_items(sequence: Node) -> Vec<Node> {
+    let sequence = unwrap(sequence);
+    let mut cursor = sequence.walk();
+    let items = sequence.named_children(&mut cursor);
+    match sequence.kind() {
+        "block_sequence" => items
+            .filter_map(|item| item.named_child(0))
+            .map(unwrap)
+            .collect(),
+        "flow_sequence" => items.map(unwrap).collect(),
+        _ => vec![],
+    }
+}
+
+/// Converts a YAML value to JSON. Anything not representable (eg aliases)
+/// becomes `null`.
+fn yaml_value(source: &str, node: Node) -> Value {
+    let node = unwrap(node);
+    match node.kind() {
+        "block_mapping" | "flow_mapping" => Value::Object(
+            pairs(source, node)
+                .into_iter()
+                .map(|(key, _, value)| {
+                    (
+                        key,
+                        value.map_or(Value::Null, |value| yaml_value(source, value)),
+                    )
+                })
+                .collect(),
+        ),
+        "block_sequence" | "flow_sequence" => Value::Array(
+            sequence_items(node)
+                .into_iter()
+                .map(|item| yaml_value(source, item))
+                .collect(),
+        ),
+        _ => serde_yaml::from_str(&source[node.byte_range()]).unwrap_or(Value::Null),
+    }
+}
+
+/// The node for the value at `path` (as in `schema::SchemaError`) in
+/// `node`, or for the whole entry when that's an object's.
+fn node_at_path<'tree>(source: &str, node: Node<'tree>, path: &[String]) -> Option<Node<'tree>> {
+    let Some((first, rest)) = path.split_first() else {
+        return Some(node);
+    };
+    let child = if is_mapping(node) {
+        let (_, key, value) = pairs(source, node)
+            .into_iter()
+            .find(|(key, _, _)| key == first)?;
+        match (rest.is_empty(), value) {
+            (false, Some(value)) => value,
+            _ => key.parent()?,
+        }
+    } else {
+        *sequence_items(node).get(first.parse::<usize>().ok()?)?
+    };
+    node_at_path(source, child, rest)
+}
+
+/// Completes whatever's being typed at `position`: a top-level key, a rule
+/// name, a rule's level or one of its options, working out where in the
+/// file that is by indentation.
+pub fn completions(
+    rope: &Rope,
+    position: Position,
+    encoding: PositionEncoding,
+    rules: &[RuleMeta],
+) -> Vec<CompletionItem> {
+    let char_index = position_to_char_index(rope, position, encoding);
+    let row = rope.char_to_line(char_index);
+    let column = rope.char_to_byte(char_index) - rope.line_to_byte(row);
+    let line = rope.line(row).to_string();
+    let (before, after) = line.split_at(column.min(line.len()));
+    let indentation = before.len() - before.trim_start().len();
+    let (value_of, start) = match before.find(':') {
+        Some(colon) => (
+            Some(before[indentation..colon].trim().trim_matches(['"', '\''])),
+            colon + 1 + (before[colon + 1..].len() - before[colon + 1..].trim_start().len()),
+        ),
+        None => (None, indentation),
+    };
+    let path = parent_keys(rope, row, indentation);
+    let path = path.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let candidates = match (value_of, path.as_slice()) {
+        (None, []) => keys(&TOP_LEVEL_KEYS),
+        (None, ["rules"]) => rules
+            .iter()
+            .map(|meta| Candidate {
+                label: format!("{}/{}", meta.plugin, meta.name),
+                kind: CompletionItemKind::PROPERTY,
+                detail: Some(meta.description.clone()),
+                documentation: meta.docs.clone(),
+            })
+            .collect(),
+        (None, ["rules", _]) => keys(&RULE_CONFIG_KEYS),
+        (None, ["rules", rule, "options", rest @ ..]) => options_schema(rules, rule)
+            .and_then(|options_schema| schema::schema_at(options_schema, rest))
+            .and_then(|schema| schema.get("properties")?.as_object())
+            .into_iter()
+            .flatten()
+            .map(|(key, schema)| Candidate {
+                label: key.clone(),
+                kind: CompletionItemKind::PROPERTY,
+                detail: schema.get("type").map(|kind| match kind {
+                    Value::String(kind) => kind.clone(),
+                    kind => kind.to_string(),
+                }),
+                documentation: schema
+                    .get("description")
+                    .and_then(Value::as_str)
+                    .map(ToOwned::to_owned),
+            })
+            .collect(),
+        (Some(_), ["rules"]) | (Some("level"), ["rules", _]) => ConfiguredLevel::NAMES
+            .iter()
+            .map(|&level| Candidate::value(level.to_owned()))
+            .collect(),
+        (Some(key), ["rules", rule, "options", rest @ ..]) => {
+            let path = [rest, &[key]].concat();
+            options_schema(rules, rule)
+                .and_then(|options_schema| schema::schema_at(options_schema, &path))
+                .map(value_candidates)
+                .unwrap_or_default()
+        }
+        _ => vec![],
+    };
+
+    let range = Range {
+        start: byte_to_position(rope, rope.line_to_byte(row) + start.min(column), encoding),
+        end: position,
+    };
+    let suffix = match value_of {
+        None if !after.contains(':') => ": ",
+        _ => "",
+    };
+    candidates
+        .into_iter()
+        .map(|candidate| CompletionItem {
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                range,
+                new_text: format!("{}{suffix}", candidate.label),
+            })),
+            kind: Some(candidate.kind),
+            detail: candidate.detail,
+            documentation: candidate.documentation.map(|documentation| {
+                Documentation::MarkupContent(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: documentation,
+                })
+            }),
+            label: candidate.label,
+            ..Default::default()
+        })
+        .collect()
+}
+
+struct Candidate {
+    label: String,
+    kind: CompletionItemKind,
+    detail: Option<String>,
+    documentation: Option<String>,
+}
+
+impl Candidate {
+    fn value(label: String) -> Self {
+        Self {
+            label,
+            kind: CompletionItemKind::ENUM_MEMBER,
+            detail: None,
+            documentation: None,
+        }
+    }
+}
+
+fn keys(keys: &[(&str, &str)]) -> Vec<Candidate> {
+    keys.iter()
+        .map(|&(key, description)| Candidate {
+            label: key.to_owned(),
+            kind: CompletionItemKind::PROPERTY,
+            detail: Some(description.to_owned()),
+            documentation: None,
+        })
+        .collect()
+}
+
+fn value_candidates(schema: &Value) -> Vec<Candidate> {
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        return allowed
+            .iter()
+            .map(|value| {
+                Candidate::value(match value {
+                    Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                })
+            })
+            .collect();
+    }
+    match schema.get("type").and_then(Value::as_str) {
+        Some("boolean") => ["true", "false"]
+            .map(|value| Candidate::value(value.to_owned()))
+            .into(),
+        _ => vec![],
+    }
+}
+
+fn options_schema<'a>(rules: &'a [RuleMeta], rule: &str) -> Option<&'a Value> {
+    rules
+        .iter()
+        .find(|meta| is_rule(rule, &meta.plugin, &meta.name))?
+        .options_schema
+        .as_ref()
+}
+
+/// The keys of the mappings enclosing an entry on `row` indented by
+/// `indentation`, outermost first.
+fn parent_keys(rope: &Rope, row: usize, mut indentation: usize) -> Vec<String> {
+    let mut keys = vec![];
+    for row in (0..row).rev() {
+        if indentation == 0 {
+            break;
+        }
+        let line = rope.line(row).to_string();
+        let trimmed = line.trim_start();
+        if trimmed.trim_end().is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let line_indentation = line.len() - trimmed.len();
+        if line_indentation >= indentation {
+            continue;
+        }
+        if let Some((key, _)) = trimmed.split_once(':') {
+            keys.push(key.trim().trim_matches(['"', '\'']).to_owned());
+        }
+        indentation = line_indentation;
+    }
+    keys.reverse();
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        language::SupportedLanguage,
+        lint::{self, Linter},
+        plugins,
+    };
+
+    fn validate_all(source: &str) -> Vec<Violation> {
+        let tree = lint::parse(source, SupportedLanguage::Yaml).unwrap();
+        validate(source, &tree, &Linter::new(plugins::all()).rules())
+    }
+
+    fn problems(source: &str) -> Vec<(String, &str)> {
+        validate_all(source)
+            .into_iter()
+            .map(|problem| {
+                let text = &source[problem.range.start_byte..problem.range.end_byte];
+                (problem.rule, text)
+            })
+            .collect()
+    }
+
+    fn labels(source: &str) -> Vec<String> {
+        let rope = Rope::from_str(source);
+        let row = rope.len_lines() - 1;
+        let position = Position {
+            line: row as u32,
+            character: rope.line(row).len_chars() as u32,
+        };
+        completions(
+            &rope,
+            position,
+            PositionEncoding::Utf16,
+            &Linter::new(plugins::all()).rules(),
+        )
+        .into_iter()
+        .map(|item| item.label)
+        .collect()
+    }
+
+    #[test]
+    fn test_validate_rules() {
+        assert_eq!(
+            problems("rules:\n  rust/no-dbg-macro: warning\n  rust/nope: error\n  rust/max-params: loud\n"),
+            vec![
+                (UNKNOWN_RULE.to_owned(), "rust/nope"),
+                (INVALID_LEVEL.to_owned(), "loud"),
+            ]
+        );
+        assert_eq!(
+            problems("rules:\n  rust/max-params:\n    level: error\n    severity: error\n"),
+            vec![(UNKNOWN_KEY.to_owned(), "severity")]
+        );
+    }
+
+    #[test]
+    fn test_validate_options_against_schema() {
+        assert_eq!(
+            problems("rules:\n  rust/max-params:\n    options:\n      max: -1\n      min: 1\n"),
+            vec![
+                (INVALID_OPTIONS.to_owned(), "max: -1"),
+                (INVALID_OPTIONS.to_owned(), "min: 1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_falls_back_to_invalid_config() {
+        let source = "rules: {}\ninclude: é3\n";
+        let [problem] = &validate_all(source)[..] else {
+            panic!("expected a single problem");
+        };
+        assert_eq!(problem.rule, INVALID_CONFIG);
+        assert!(problem.range.start_byte > source.find("include").unwrap());
+        assert!(source.is_char_boundary(problem.range.start_byte));
+
+        // Only once everything else is fine.
+        assert_eq!(
+            problems("rules:\n  rust/nope: error\ninclude: 3\n"),
+            vec![(UNKNOWN_RULE.to_owned(), "rust/nope")]
+        );
+    }
+
+    #[test]
+    fn test_completions() {
+        assert!(labels("").contains(&"rules".to_owned()));
+        assert!(labels("rules:\n  ").contains(&"rust/max-params".to_owned()));
+        assert_eq!(
+            labels("rules:\n  rust/max-params:\n    "),
+            vec!["level", "options"]
+        );
+        assert_eq!(
+            labels("rules:\n  rust/max-params:\n    options:\n      "),
+            vec!["max"]
+        );
+        assert_eq!(
+            labels("rules:\n  rust/max-params: "),
+            ConfiguredLevel::NAMES
+        );
+        assert_eq!(
+            labels("rules:\n  rust/max-params:\n    level: "),
+            ConfiguredLevel::NAMES
+        );
+    }
+}
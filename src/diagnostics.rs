@@ -0,0 +1,145 @@
+use ropey::Rope;
+use tower_lsp::lsp_types::{
+    CodeDescription, Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, DiagnosticTag,
+    FullDocumentDiagnosticReport, Location, NumberOrString, Position, Range,
+    UnchangedDocumentDiagnosticReport, Url,
+};
+
+use crate::{
+    config::ConfigError,
+    document::{next_result_id, Document},
+    position::{range_to_lsp, PositionEncoding},
+    violation::{RuleLevel, Violation, ViolationTag},
+};
+
+/// `rope` is the text of the document `violation` was reported in (`uri`).
+pub fn violation_to_diagnostic(
+    violation: &Violation,
+    uri: &Url,
+    rope: &Rope,
+    encoding: PositionEncoding,
+) -> Diagnostic {
+    Diagnostic {
+        range: range_to_lsp(rope, &violation.range, encoding),
+        severity: Some(level_to_severity(violation.level)),
+        code: Some(NumberOrString::String(violation.rule.clone())),
+        code_description: violation
+            .docs_url
+            .as_deref()
+            .and_then(|docs_url| Url::parse(docs_url).ok())
+            .map(|href| CodeDescription { href }),
+        source: Some(violation.plugin.clone()),
+        message: violation.message.clone(),
+        tags: (!violation.tags.is_empty())
+            .then(|| violation.tags.iter().copied().map(tag_to_lsp).collect()),
+        related_information: (!violation.related.is_empty()).then(|| {
+            violation
+                .related
+                .iter()
+                .map(|related| DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: uri.clone(),
+                        range: range_to_lsp(rope, &related.range, encoding),
+                    },
+                    message: related.message.clone(),
+                })
+                .collect()
+        }),
+        ..Default::default()
+    }
+}
+
+fn tag_to_lsp(tag: ViolationTag) -> DiagnosticTag {
+    match tag {
+        ViolationTag::Unnecessary => DiagnosticTag::UNNECESSARY,
+        ViolationTag::Deprecated => DiagnosticTag::DEPRECATED,
+    }
+}
+
+fn level_to_severity(level: RuleLevel) -> DiagnosticSeverity {
+    match level {
+        RuleLevel::Error => DiagnosticSeverity::ERROR,
+        RuleLevel::Warning => DiagnosticSeverity::WARNING,
+        RuleLevel::Information => DiagnosticSeverity::INFORMATION,
+        RuleLevel::Hint => DiagnosticSeverity::HINT,
+    }
+}
+
+pub fn config_error_to_diagnostic(error: &ConfigError) -> Diagnostic {
+    let (line, column) = error.location().unwrap_or_default();
+    let position = Position {
+        line: line as u32,
+        character: column as u32,
+    };
+    Diagnostic {
+        range: Range {
+            start: position,
+            end: position,
+        },
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some("tree-sitter-lint".to_owned()),
+        message: error.to_string(),
+        ..Default::default()
+    }
+}
+
+pub enum PullReport {
+    Full(FullDocumentDiagnosticReport),
+    Unchanged(UnchangedDocumentDiagnosticReport),
+}
+
+/// Unchanged if the client already has `document`'s results, as long as
+/// they're of its current version or `keep_stale` (eg with `run: onSave`,
+/// when results stand until the next save).
+pub fn pull_report(
+    uri: &Url,
+    document: &Document,
+    previous_result_id: Option<&str>,
+    keep_stale: bool,
+    encoding: PositionEncoding,
+) -> PullReport {
+    let is_current = keep_stale || document.violations_version == Some(document.version);
+    match &document.result_id {
+        Some(result_id) if is_current && previous_result_id == Some(result_id) => {
+            PullReport::Unchanged(UnchangedDocumentDiagnosticReport {
+                result_id: result_id.clone(),
+            })
+        }
+        result_id => PullReport::Full(FullDocumentDiagnosticReport {
+            result_id: result_id.clone(),
+            items: document
+                .current_violations()
+                .iter()
+                .map(|violation| violation_to_diagnostic(violation, uri, &document.rope, encoding))
+                .collect(),
+        }),
+    }
+}
+
+/// The diagnostics of a file that isn't open, from linting the workspace,
+/// for pull diagnostics clients.
+pub struct FileReport {
+    pub result_id: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl FileReport {
+    pub fn new(diagnostics: Vec<Diagnostic>) -> Self {
+        Self {
+            result_id: next_result_id(),
+            diagnostics,
+        }
+    }
+
+    pub fn pull_report(&self, previous_result_id: Option<&str>) -> PullReport {
+        if previous_result_id == Some(&self.result_id) {
+            return PullReport::Unchanged(UnchangedDocumentDiagnosticReport {
+                result_id: self.result_id.clone(),
+            });
+        }
+        PullReport::Full(FullDocumentDiagnosticReport {
+            result_id: Some(self.result_id.clone()),
+            items: self.diagnostics.clone(),
+        })
+    }
+}
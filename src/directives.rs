@@ -0,0 +1,555 @@
+//! Comments that turn rules off, eg:
+//!
+//! ```text
+//! // tree-sitter-lint-disable rust/no-todo-macro
+//! // tree-sitter-lint-disable-next-line rust/no-dbg-macro, rust/max-params -- reason
+//! ```
+//!
+//! `tree-sitter-lint-disable` applies to the whole file and
+//! `tree-sitter-lint-disable-next-line` to the line following the comment.
+//! Listing no rules disables all of them.
+//!
+//! Directives that don't end up disabling anything, or that name rules that
+//! don't exist, are themselves reported (as hints, with a fix removing them).
+
+use std::ops::Range;
+
+use tree_sitter::Tree;
+
+use crate::{
+    injections,
+    language::SupportedLanguage,
+    rule_queries::INVALID_RULE_QUERY,
+    violation::{byte_range_to_range, Edit, RuleLevel, Violation, ViolationTag},
+};
+
+/// The plugin name problems with directives are reported under.
+pub const PLUGIN: &str = "tree-sitter-lint";
+pub const UNUSED_DIRECTIVE: &str = "unused-disable-directive";
+pub const UNKNOWN_RULE: &str = "unknown-rule-in-disable-directive";
+
+pub const DISABLE: &str = "tree-sitter-lint-disable";
+pub const DISABLE_NEXT_LINE: &str = "tree-sitter-lint-disable-next-line";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DirectiveKind {
+    File,
+    NextLine,
+}
+
+impl DirectiveKind {
+    pub fn keyword(self) -> &'static str {
+        match self {
+            Self::File => DISABLE,
+            Self::NextLine => DISABLE_NEXT_LINE,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Directive {
+    pub kind: DirectiveKind,
+    /// The zero-based line the comment is on.
+    pub line: usize,
+    /// Byte offset of the start of that line.
+    pub line_start: usize,
+    /// Byte range of the whole comment.
+    pub comment: Range<usize>,
+    /// The rule names (and their byte ranges), as written.
+    pub rules: Vec<(String, Range<usize>)>,
+    /// Byte offset just past the last rule name (or the keyword, if there
+    /// aren't any), where more rules can be added.
+    pub rules_end: usize,
+}
+
+impl Directive {
+    /// If this directive disables `violation`, returns the index of the rule
+    /// name that did it (`0` if no rules are listed).
+    fn suppressing_rule(&self, violation: &Violation) -> Option<usize> {
+        let applies = match self.kind {
+            DirectiveKind::File => true,
+            DirectiveKind::NextLine => violation.range.start_point.row == self.line + 1,
+        };
+        if !applies {
+            return None;
+        }
+        if self.rules.is_empty() {
+            return Some(0);
+        }
+        self.rules
+            .iter()
+            .position(|(rule, _)| is_rule(rule, &violation.plugin, &violation.rule))
+    }
+
+    /// Reports unknown rule names, and whichever parts of the directive
+    /// didn't suppress anything according to `used` (indexed like
+    /// `suppressing_rule()`).
+    fn problems(
+        &self,
+        source: &str,
+        used: &[bool],
+        is_known_rule: &impl Fn(&str) -> bool,
+    ) -> Vec<Violation> {
+        let mut problems = vec![];
+        for (index, (rule, range)) in self.rules.iter().enumerate() {
+            if !is_known_rule(rule) {
+                problems.push(self.problem(
+                    source,
+                    UNKNOWN_RULE,
+                    format!(
+                        "Unknown rule `{rule}` in {} directive.",
+                        self.kind.keyword()
+                    ),
+                    range.clone(),
+                    self.rule_removal(source, index),
+                ));
+            }
+        }
+        if !used.contains(&true) {
+            problems.push(self.problem(
+                source,
+                UNUSED_DIRECTIVE,
+                format!(
+                    "Unused {} directive (no problems were reported).",
+                    self.kind.keyword()
+                ),
+                self.comment.clone(),
+                self.removal(source),
+            ));
+            return problems;
+        }
+        for (index, (rule, range)) in self.rules.iter().enumerate() {
+            if !used[index] && is_known_rule(rule) {
+                problems.push(self.problem(
+                    source,
+                    UNUSED_DIRECTIVE,
+                    format!(
+                        "Unused {} directive (no problems were reported from `{rule}`).",
+                        self.kind.keyword()
+                    ),
+                    range.clone(),
+                    self.rule_removal(source, index),
+                ));
+            }
+        }
+        problems
+    }
+
+    /// Removes the whole comment, along with its line if nothing else is on
+    /// it.
+    fn removal(&self, source: &str) -> Range<usize> {
+        let before = &source[self.line_start..self.comment.start];
+        let line_end = source[self.comment.end..]
+            .find('\n')
+            .map_or(source.len(), |index| self.comment.end + index + 1);
+        if before.trim().is_empty() && source[self.comment.end..line_end].trim().is_empty() {
+            return self.line_start..line_end;
+        }
+        self.line_start + before.trim_end().len()..self.comment.end
+    }
+
+    /// Removes the `index`th rule name along with its separating comma, or
+    /// the whole comment if it's the only one.
+    fn rule_removal(&self, source: &str, index: usize) -> Range<usize> {
+        if self.rules.len() == 1 {
+            return self.removal(source);
+        }
+        let range = &self.rules[index].1;
+        match index {
+            0 => range.start..self.rules[1].1.start,
+            _ => self.rules[index - 1].1.end..range.end,
+        }
+    }
+
+    fn problem(
+        &self,
+        source: &str,
+        rule: &str,
+        message: String,
+        range: Range<usize>,
+        removal: Range<usize>,
+    ) -> Violation {
+        Violation {
+            fix: Some(vec![Edit {
+                range: byte_range_to_range(source, removal),
+                replacement: String::new(),
+            }]),
+            tags: match rule {
+                UNUSED_DIRECTIVE => vec![ViolationTag::Unnecessary],
+                _ => vec![],
+            },
+            ..Violation::internal(source, rule, RuleLevel::Hint, message, range)
+        }
+    }
+}
+
+pub fn is_rule(name: &str, plugin: &str, rule: &str) -> bool {
+    name.strip_prefix(plugin)
+        .and_then(|rest| rest.strip_prefix('/'))
+        == Some(rule)
+}
+
+/// Finds the directives in the comments of `tree` (`source` parsed as
+/// `language`) and of any languages embedded in it, written in the comment
+/// syntax of the language they're in.
+pub fn parse(source: &str, tree: &Tree, language: SupportedLanguage) -> Vec<Directive> {
+    if !source.contains(DISABLE) {
+        return vec![];
+    }
+    let mut comments = vec![];
+    find_comments(source, tree, language, 0, &mut comments);
+    comments.sort_by_key(|(range, _)| range.start);
+    comments.dedup_by_key(|(range, _)| range.start);
+    comments
+        .into_iter()
+        .filter_map(|(range, language)| parse_comment(source, range, language.comment_syntax()))
+        .collect()
+}
+
+/// Collects the comments that could be directives (ie mention `DISABLE`),
+/// following injections `MAX_DEPTH` deep.
+fn find_comments(
+    source: &str,
+    tree: &Tree,
+    language: SupportedLanguage,
+    depth: usize,
+    comments: &mut Vec<(Range<usize>, SupportedLanguage)>,
+) {
+    let mut cursor = tree.walk();
+    'walk: loop {
+        let node = cursor.node();
+        if source[node.byte_range()].contains(DISABLE) {
+            if node.kind().contains("comment") {
+                comments.push((node.byte_range(), language));
+            } else if cursor.goto_first_child() {
+                continue;
+            }
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'walk;
+            }
+        }
+    }
+    if depth >= injections::MAX_DEPTH {
+        return;
+    }
+    for injection in injections::find(source, tree, language) {
+        if let Some(injected_tree) = injection.parse(source) {
+            find_comments(
+                source,
+                &injected_tree,
+                injection.language,
+                depth + 1,
+                comments,
+            );
+        }
+    }
+}
+
+/// Parses the comment at `range` in `source`, if it's a directive (on a
+/// single line).
+fn parse_comment(source: &str, range: Range<usize>, comment: CommentSyntax) -> Option<Directive> {
+    let text = source[range.clone()].trim_end_matches(['\n', '\r']);
+    if text.contains('\n') {
+        return None;
+    }
+    let comment_start = range.start;
+    let comment_end = comment_start + text.len();
+    let body = text.strip_prefix(comment.start)?;
+    let body = match comment.end {
+        "" => body,
+        end => body.strip_suffix(end)?,
+    };
+    let body_start = comment_start + comment.start.len() + body.len() - body.trim_start().len();
+    let body = body.trim_start();
+
+    let (kind, rest) = [DirectiveKind::NextLine, DirectiveKind::File]
+        .into_iter()
+        .find_map(|kind| Some((kind, body.strip_prefix(kind.keyword())?)))?;
+    if !(rest.is_empty() || rest.starts_with(char::is_whitespace)) {
+        return None;
+    }
+    let rest_start = body_start + kind.keyword().len();
+    let rest = rest.split(" --").next().unwrap();
+
+    let mut rules = vec![];
+    let mut rules_end = rest_start;
+    let mut offset = rest_start;
+    for part in rest.split(',') {
+        let trimmed_start = part.len() - part.trim_start().len();
+        let name = part.trim();
+        if !name.is_empty() {
+            let start = offset + trimmed_start;
+            rules.push((name.to_owned(), start..start + name.len()));
+            rules_end = start + name.len();
+        }
+        offset += part.len() + 1;
+    }
+
+    Some(Directive {
+        kind,
+        line: source[..comment_start].matches('\n').count(),
+        line_start: source[..comment_start]
+            .rfind('\n')
+            .map_or(0, |index| index + 1),
+        comment: comment_start..comment_end,
+        rules,
+        rules_end,
+    })
+}
+
+/// Drops the violations disabled by a directive in `source` (parsed as
+/// `tree`), and adds problems with the directives themselves.
+/// `is_known_rule` is passed rule names as written in directives
+/// (`plugin/rule`).
+pub fn apply(
+    source: &str,
+    tree: &Tree,
+    language: SupportedLanguage,
+    violations: Vec<Violation>,
+    is_known_rule: impl Fn(&str) -> bool,
+) -> Vec<Violation> {
+    let directives = parse(source, tree, language);
+    if directives.is_empty() {
+        return violations;
+    }
+    let is_known_rule =
+        |name: &str| is_rule(name, PLUGIN, INVALID_RULE_QUERY) || is_known_rule(name);
+    let mut used = directives
+        .iter()
+        .map(|directive| vec![false; directive.rules.len().max(1)])
+        .collect::<Vec<_>>();
+    let mut kept = vec![];
+    for violation in violations {
+        let mut suppressed = false;
+        for (directive, used) in directives.iter().zip(&mut used) {
+            if let Some(index) = directive.suppressing_rule(&violation) {
+                used[index] = true;
+                suppressed = true;
+            }
+        }
+        if !suppressed {
+            kept.push(violation);
+        }
+    }
+    for (directive, used) in directives.iter().zip(&used) {
+        kept.extend(directive.problems(source, used, &is_known_rule));
+    }
+    kept.sort_by_key(|violation| violation.range.start_byte);
+    kept
+}
+
+/// Whether `violation` is one of `apply()`'s problems with a directive.
+pub fn is_directive_problem(violation: &Violation) -> bool {
+    violation.plugin == PLUGIN && matches!(violation.rule.as_str(), UNUSED_DIRECTIVE | UNKNOWN_RULE)
+}
+
+/// How to write a comment in a given language.
+#[derive(Copy, Clone, Debug)]
+pub struct CommentSyntax {
+    pub start: &'static str,
+    /// Empty for line comments.
+    pub end: &'static str,
+}
+
+impl CommentSyntax {
+    pub fn directive(self, kind: DirectiveKind, rule: &str) -> String {
+        match self.end {
+            "" => format!("{} {} {rule}", self.start, kind.keyword()),
+            end => format!("{} {} {rule} {end}", self.start, kind.keyword()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lint::{self, Linter},
+        plugins,
+    };
+
+    fn parse_as(source: &str, language: SupportedLanguage) -> Vec<Directive> {
+        parse(source, &lint::parse(source, language).unwrap(), language)
+    }
+
+    fn rules(directive: &Directive) -> Vec<&str> {
+        directive
+            .rules
+            .iter()
+            .map(|(rule, _)| rule.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_ignores_directives_in_strings() {
+        let source = "let s = \"\n// tree-sitter-lint-disable\n\";\ndbg!(s);\n";
+        assert!(parse_as(source, SupportedLanguage::Rust).is_empty());
+    }
+
+    #[test]
+    fn test_parse_after_comment_start_in_string() {
+        let source = "call(\"http://example.com\"); // tree-sitter-lint-disable-next-line rust/no-dbg-macro\ndbg!(1);\n";
+        let directives = parse_as(source, SupportedLanguage::Rust);
+        assert_eq!(directives.len(), 1);
+        let directive = &directives[0];
+        assert_eq!(directive.kind, DirectiveKind::NextLine);
+        assert_eq!(directive.line, 0);
+        assert_eq!(
+            directive.comment,
+            source.find("// tree").unwrap()..source.find('\n').unwrap()
+        );
+        assert_eq!(rules(directive), ["rust/no-dbg-macro"]);
+        assert_eq!(&source[directive.rules[0].1.clone()], "rust/no-dbg-macro");
+    }
+
+    #[test]
+    fn test_parse_in_injected_language() {
+        let source = "<p>\n<!-- tree-sitter-lint-disable -->\n<script>\n  // tree-sitter-lint-disable-next-line javascript/no-debugger -- why\n  debugger;\n</script>\n";
+        let directives = parse_as(source, SupportedLanguage::Html);
+        assert_eq!(directives.len(), 2);
+        assert_eq!(directives[0].kind, DirectiveKind::File);
+        assert_eq!(directives[0].line, 1);
+        assert!(directives[0].rules.is_empty());
+        assert_eq!(directives[0].rules_end, source.find(" -->").unwrap());
+        assert_eq!(directives[1].kind, DirectiveKind::NextLine);
+        assert_eq!(directives[1].line, 3);
+        assert_eq!(directives[1].line_start, source.find("  //").unwrap());
+        assert_eq!(rules(&directives[1]), ["javascript/no-debugger"]);
+    }
+
+    /// The problems with `source`'s directives: their rule, the text they're
+    /// reported at, and `source` with their fix applied.
+    fn problems(source: &str) -> Vec<(&'static str, String, String)> {
+        let tree = lint::parse(source, SupportedLanguage::Rust).unwrap();
+        Linter::new(plugins::all())
+            .lint(source, &tree, SupportedLanguage::Rust, &Default::default())
+            .into_iter()
+            .filter(|violation| violation.plugin == PLUGIN)
+            .map(|violation| {
+                let rule = match violation.rule.as_str() {
+                    UNUSED_DIRECTIVE => UNUSED_DIRECTIVE,
+                    UNKNOWN_RULE => UNKNOWN_RULE,
+                    rule => panic!("unexpected rule {rule}"),
+                };
+                let [edit] = &violation.fix.unwrap()[..] else {
+                    panic!("expected a single edit");
+                };
+                let mut fixed = source.to_owned();
+                fixed.replace_range(
+                    edit.range.start_byte..edit.range.end_byte,
+                    &edit.replacement,
+                );
+                (
+                    rule,
+                    source[violation.range.start_byte..violation.range.end_byte].to_owned(),
+                    fixed,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_used_directives() {
+        assert_eq!(
+            problems(
+                "// tree-sitter-lint-disable rust/no-todo-macro\n// tree-sitter-lint-disable-next-line\ndbg!(1); todo!();\n"
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_unused_directive_removes_its_line() {
+        assert_eq!(
+            problems("fn f() {\n    // tree-sitter-lint-disable-next-line rust/no-dbg-macro\n    g();\n}\n"),
+            vec![(
+                UNUSED_DIRECTIVE,
+                "// tree-sitter-lint-disable-next-line rust/no-dbg-macro".to_owned(),
+                "fn f() {\n    g();\n}\n".to_owned()
+            )]
+        );
+        assert_eq!(
+            problems("// tree-sitter-lint-disable\nfn f() {}\n"),
+            vec![(
+                UNUSED_DIRECTIVE,
+                "// tree-sitter-lint-disable".to_owned(),
+                "fn f() {}\n".to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_unused_directive_after_code_keeps_the_code() {
+        assert_eq!(
+            problems("g(); // tree-sitter-lint-disable rust/no-dbg-macro\n"),
+            vec![(
+                UNUSED_DIRECTIVE,
+                "// tree-sitter-lint-disable rust/no-dbg-macro".to_owned(),
+                "g();\n".to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_unused_rules_are_removed_with_their_comma() {
+        let directive = "// tree-sitter-lint-disable-next-line";
+        assert_eq!(
+            problems(&format!(
+                "{directive} rust/no-todo-macro, rust/no-dbg-macro\ndbg!(1);\n"
+            )),
+            vec![(
+                UNUSED_DIRECTIVE,
+                "rust/no-todo-macro".to_owned(),
+                format!("{directive} rust/no-dbg-macro\ndbg!(1);\n")
+            )]
+        );
+        assert_eq!(
+            problems(&format!(
+                "{directive} rust/no-dbg-macro, rust/max-params, rust/no-todo-macro\ndbg!(1); todo!();\n"
+            )),
+            vec![(
+                UNUSED_DIRECTIVE,
+                "rust/max-params".to_owned(),
+                format!("{directive} rust/no-dbg-macro, rust/no-todo-macro\ndbg!(1); todo!();\n")
+            )]
+        );
+        assert_eq!(
+            problems(&format!(
+                "{directive} rust/no-dbg-macro, rust/no-todo-macro -- because\ndbg!(1);\n"
+            )),
+            vec![(
+                UNUSED_DIRECTIVE,
+                "rust/no-todo-macro".to_owned(),
+                format!("{directive} rust/no-dbg-macro -- because\ndbg!(1);\n")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_unknown_rules() {
+        let directive = "// tree-sitter-lint-disable-next-line";
+        assert_eq!(
+            problems(&format!(
+                "{directive} rust/no-dbg-macro, rust/nope\ndbg!(1);\n"
+            )),
+            vec![(
+                UNKNOWN_RULE,
+                "rust/nope".to_owned(),
+                format!("{directive} rust/no-dbg-macro\ndbg!(1);\n")
+            )]
+        );
+        assert_eq!(
+            problems(&format!("{directive} rust/nope -- why\ng();\n")),
+            vec![
+                (
+                    UNUSED_DIRECTIVE,
+                    format!("{directive} rust/nope -- why"),
+                    "g();\n".to_owned()
+                ),
+                (UNKNOWN_RULE, "rust/nope".to_owned(), "g();\n".to_owned()),
+            ]
+        );
+    }
+}
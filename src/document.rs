@@ -0,0 +1,268 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use ropey::Rope;
+use tower_lsp::lsp_types::TextDocumentContentChangeEvent;
+use tree_sitter::{InputEdit, Parser, Tree};
+
+use crate::{
+    language::SupportedLanguage,
+    position::{char_index_to_point, position_to_char_index, PositionEncoding},
+    violation::Violation,
+};
+
+pub struct Document {
+    pub rope: Rope,
+    pub version: i32,
+    pub language: SupportedLanguage,
+    pub tree: Option<Tree>,
+    pub violations: Vec<Violation>,
+    /// The version `violations` were computed for. Their ranges only fit
+    /// `rope` while it's still `version`.
+    pub violations_version: Option<i32>,
+    /// Identifies the current `violations` for pull diagnostics. `None` while
+    /// they're stale because the config changed since they were computed
+    /// (edits are caught by `violations_version`).
+    pub result_id: Option<String>,
+}
+
+static NEXT_RESULT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A fresh id for a set of pull diagnostics results.
+pub fn next_result_id() -> String {
+    NEXT_RESULT_ID.fetch_add(1, Ordering::Relaxed).to_string()
+}
+
+impl Document {
+    pub fn new(text: &str, version: i32, language: SupportedLanguage) -> Self {
+        let mut document = Self {
+            rope: Rope::from_str(text),
+            version,
+            language,
+            tree: None,
+            violations: Default::default(),
+            violations_version: None,
+            result_id: None,
+        };
+        document.reparse();
+        document
+    }
+
+    pub fn text(&self) -> String {
+        self.rope.to_string()
+    }
+
+    /// Stores `violations` as those of the current version.
+    pub fn set_violations(&mut self, violations: Vec<Violation>) {
+        self.violations = violations;
+        self.violations_version = Some(self.version);
+        self.result_id = Some(next_result_id());
+    }
+
+    /// The violations, unless they're of an older version (eg while waiting
+    /// to lint again, or until saved with `run: onSave`), when they can't be
+    /// mapped onto the current text.
+    pub fn current_violations(&self) -> &[Violation] {
+        match self.violations_version {
+            Some(version) if version == self.version => &self.violations,
+            _ => &[],
+        }
+    }
+
+    /// Applies `changes` in order, editing the existing tree to match so
+    /// that the following reparse can reuse it.
+    pub fn apply_changes(
+        &mut self,
+        changes: Vec<TextDocumentContentChangeEvent>,
+        version: i32,
+        encoding: PositionEncoding,
+    ) {
+        for change in changes {
+            self.apply_change(change, encoding);
+        }
+        self.version = version;
+        self.reparse();
+    }
+
+    fn apply_change(&mut self, change: TextDocumentContentChangeEvent, encoding: PositionEncoding) {
+        let Some(range) = change.range else {
+            self.rope = Rope::from_str(&change.text);
+            self.tree = None;
+            return;
+        };
+
+        let start_char = position_to_char_index(&self.rope, range.start, encoding);
+        let old_end_char = position_to_char_index(&self.rope, range.end, encoding).max(start_char);
+        let start_byte = self.rope.char_to_byte(start_char);
+        let old_end_byte = self.rope.char_to_byte(old_end_char);
+        let start_position = char_index_to_point(&self.rope, start_char);
+        let old_end_position = char_index_to_point(&self.rope, old_end_char);
+
+        self.rope.remove(start_char..old_end_char);
+        self.rope.insert(start_char, &change.text);
+
+        let new_end_char = start_char + change.text.chars().count();
+        if let Some(tree) = self.tree.as_mut() {
+            tree.edit(&InputEdit {
+                start_byte,
+                old_end_byte,
+                new_end_byte: start_byte + change.text.len(),
+                start_position,
+                old_end_position,
+                new_end_position: char_index_to_point(&self.rope, new_end_char),
+            });
+        }
+    }
+
+    fn reparse(&mut self) {
+        let mut parser = Parser::new();
+        if parser.set_language(&self.language.language()).is_err() {
+            self.tree = None;
+            return;
+        }
+        let rope = &self.rope;
+        self.tree = parser.parse_with(
+            &mut |byte_index, _| {
+                if byte_index >= rope.len_bytes() {
+                    return &[] as &[u8];
+                }
+                let (chunk, chunk_byte_index, _, _) = rope.chunk_at_byte(byte_index);
+                &chunk.as_bytes()[byte_index - chunk_byte_index..]
+            },
+            self.tree.as_ref(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{Position, Range};
+
+    use super::*;
+    use crate::{lint::Linter, plugins};
+
+    fn change(start: (u32, u32), end: (u32, u32), text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: Some(Range {
+                start: Position {
+                    line: start.0,
+                    character: start.1,
+                },
+                end: Position {
+                    line: end.0,
+                    character: end.1,
+                },
+            }),
+            range_length: None,
+            text: text.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_apply_changes_after_emoji_and_cjk() {
+        let text = "let s = \"😀\"; dbg!(s);\n// 日本語 x\n";
+        for (encoding, emoji_line_dbg, cjk_line_x) in [
+            (PositionEncoding::Utf8, 16, 13),
+            (PositionEncoding::Utf16, 14, 7),
+            (PositionEncoding::Utf32, 13, 7),
+        ] {
+            let mut document = Document::new(text, 1, SupportedLanguage::Rust);
+            document.apply_changes(
+                vec![
+                    change((0, emoji_line_dbg), (0, emoji_line_dbg + 3), "println"),
+                    change((1, cjk_line_x), (1, cjk_line_x + 1), "y"),
+                ],
+                2,
+                encoding,
+            );
+            assert_eq!(
+                document.text(),
+                "let s = \"😀\"; println!(s);\n// 日本語 y\n",
+                "{encoding:?}"
+            );
+            assert_eq!(
+                document.tree.as_ref().unwrap().root_node().to_sexp(),
+                Document::new(&document.text(), 2, SupportedLanguage::Rust)
+                    .tree
+                    .unwrap()
+                    .root_node()
+                    .to_sexp(),
+                "{encoding:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_apply_changes_after_form_feed_and_line_separator() {
+        // Neither LSP nor tree-sitter end lines at these.
+        let text = "// a\u{c}b\u{2028}c\nlet x = dbg!(1);\n";
+        let mut document = Document::new(text, 1, SupportedLanguage::Rust);
+        document.apply_changes(
+            vec![change((1, 8), (1, 11), "foo")],
+            2,
+            PositionEncoding::Utf16,
+        );
+        assert_eq!(document.text(), "// a\u{c}b\u{2028}c\nlet x = foo!(1);\n");
+        assert_eq!(
+            document.tree.as_ref().unwrap().root_node().to_sexp(),
+            Document::new(&document.text(), 2, SupportedLanguage::Rust)
+                .tree
+                .unwrap()
+                .root_node()
+                .to_sexp()
+        );
+    }
+
+    #[test]
+    fn test_apply_changes_after_lone_cr() {
+        // A new LSP line, but not a new tree-sitter row.
+        let text = "// a\rlet x = dbg!(1);\n";
+        let mut document = Document::new(text, 1, SupportedLanguage::Rust);
+        document.apply_changes(
+            vec![change((1, 8), (1, 11), "foo")],
+            2,
+            PositionEncoding::Utf16,
+        );
+        assert_eq!(document.text(), "// a\rlet x = foo!(1);\n");
+        let macro_position = |tree: &Tree| {
+            let byte = text.find("(1)").unwrap();
+            let node = tree
+                .root_node()
+                .descendant_for_byte_range(byte, byte)
+                .unwrap();
+            node.start_position()
+        };
+        assert_eq!(
+            macro_position(document.tree.as_ref().unwrap()),
+            macro_position(
+                &Document::new(&document.text(), 2, SupportedLanguage::Rust)
+                    .tree
+                    .unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_violations_are_stale_after_changes() {
+        let text = "fn f() { dbg!(1); }\n";
+        let mut document = Document::new(text, 1, SupportedLanguage::Rust);
+        let tree = document.tree.clone().unwrap();
+        let violations = Linter::new(plugins::all()).lint(
+            text,
+            &tree,
+            SupportedLanguage::Rust,
+            &Default::default(),
+        );
+        assert!(!violations.is_empty());
+        document.set_violations(violations);
+        assert_eq!(
+            document.current_violations().len(),
+            document.violations.len()
+        );
+        document.apply_changes(
+            vec![change((0, 0), (0, 0), "\n")],
+            2,
+            PositionEncoding::Utf16,
+        );
+        assert!(document.current_violations().is_empty());
+    }
+}
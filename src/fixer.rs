@@ -0,0 +1,59 @@
+use tree_sitter::{Node, Range};
+
+use crate::violation::Edit;
+
+#[derive(Default)]
+pub struct Fixer {
+    edits: Vec<Edit>,
+}
+
+impl Fixer {
+    pub fn replace_text(&mut self, node: Node, replacement: impl Into<String>) {
+        self.replace_text_range(node.range(), replacement);
+    }
+
+    pub fn replace_text_range(&mut self, range: Range, replacement: impl Into<String>) {
+        self.edits.push(Edit {
+            range,
+            replacement: replacement.into(),
+        });
+    }
+
+    pub fn remove(&mut self, node: Node) {
+        self.remove_range(node.range());
+    }
+
+    pub fn remove_range(&mut self, range: Range) {
+        self.replace_text_range(range, "");
+    }
+
+    pub fn insert_text_before(&mut self, node: Node, text: impl Into<String>) {
+        self.replace_text_range(empty_range_at_start(node), text);
+    }
+
+    pub fn insert_text_after(&mut self, node: Node, text: impl Into<String>) {
+        self.replace_text_range(empty_range_at_end(node), text);
+    }
+
+    pub fn into_edits(self) -> Vec<Edit> {
+        self.edits
+    }
+}
+
+fn empty_range_at_start(node: Node) -> Range {
+    Range {
+        start_byte: node.start_byte(),
+        end_byte: node.start_byte(),
+        start_point: node.start_position(),
+        end_point: node.start_position(),
+    }
+}
+
+fn empty_range_at_end(node: Node) -> Range {
+    Range {
+        start_byte: node.end_byte(),
+        end_byte: node.end_byte(),
+        start_point: node.end_position(),
+        end_point: node.end_position(),
+    }
+}
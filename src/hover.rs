@@ -0,0 +1,33 @@
+use serde_json::Value;
+
+use crate::rule::RuleMeta;
+
+pub fn rule_hover_markdown(
+    plugin: &str,
+    rule: &str,
+    meta: Option<&RuleMeta>,
+    options: Option<&Value>,
+) -> String {
+    let mut markdown = format!("**{plugin}/{rule}**");
+    if meta.is_some_and(|meta| meta.fixable) {
+        markdown.push_str(" _(fixable)_");
+    }
+    if let Some(meta) = meta {
+        markdown.push_str(&format!("\n\n{}", meta.description));
+    }
+    if let Some(options) = options {
+        markdown.push_str(&format!(
+            "\n\nOptions in effect:\n\n```json\n{}\n```",
+            serde_json::to_string_pretty(options).unwrap()
+        ));
+    } else if let Some(default_options) = meta.and_then(RuleMeta::default_options) {
+        markdown.push_str(&format!(
+            "\n\nDefault options:\n\n```json\n{}\n```",
+            serde_json::to_string_pretty(&default_options).unwrap()
+        ));
+    }
+    if let Some(docs) = meta.and_then(|meta| meta.docs.as_deref()) {
+        markdown.push_str(&format!("\n\n---\n\n{docs}"));
+    }
+    markdown
+}
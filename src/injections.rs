@@ -0,0 +1,279 @@
+//! Finds ranges of a document written in another language (eg `<script>`s
+//! in HTML), going by each language's tree-sitter injection query.
+//!
+//! Embedded ranges are parsed with `Parser::set_included_ranges()` over the
+//! whole host document, so the resulting trees' nodes (and so any violations
+//! reported against them) are already in the host document's coordinates.
+
+use std::{collections::HashMap, sync::OnceLock};
+
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{Node, Parser, Query, QueryCursor, QueryMatch, Range, Tree};
+
+use crate::language::SupportedLanguage;
+
+/// How deeply injections are followed (eg HTML containing JavaScript
+/// containing CSS is two deep).
+pub const MAX_DEPTH: usize = 4;
+
+const CONTENT: &str = "injection.content";
+const LANGUAGE: &str = "injection.language";
+const COMBINED: &str = "injection.combined";
+const INCLUDE_CHILDREN: &str = "injection.include-children";
+
+#[derive(Debug)]
+pub struct Injection {
+    pub language: SupportedLanguage,
+    /// Sorted and non-overlapping.
+    pub ranges: Vec<Range>,
+}
+
+impl Injection {
+    pub fn parse(&self, source: &str) -> Option<Tree> {
+        let mut parser = Parser::new();
+        parser.set_language(&self.language.language()).ok()?;
+        parser.set_included_ranges(&self.ranges).ok()?;
+        parser.parse(source, None)
+    }
+}
+
+fn query(language: SupportedLanguage) -> Option<&'static Query> {
+    static QUERIES: OnceLock<HashMap<SupportedLanguage, Query>> = OnceLock::new();
+    QUERIES
+        .get_or_init(|| {
+            SupportedLanguage::ALL
+                .into_iter()
+                .filter_map(|language| {
+                    let query =
+                        Query::new(&language.language(), language.injections_query()?).ok()?;
+                    Some((language, query))
+                })
+                .collect()
+        })
+        .get(&language)
+}
+
+/// Finds the languages injected into `tree` (not following them any
+/// further).
+pub fn find(source: &str, tree: &Tree, language: SupportedLanguage) -> Vec<Injection> {
+    let Some(query) = query(language) else {
+        return vec![];
+    };
+    let mut injections = vec![];
+    let mut combined: HashMap<(usize, SupportedLanguage), Vec<Range>> = HashMap::new();
+    let mut query_cursor = QueryCursor::new();
+    let mut matches = query_cursor.matches(query, tree.root_node(), source.as_bytes());
+    while let Some(query_match) = matches.next() {
+        let Some((language, content)) = match_language_and_content(query, query_match, source)
+        else {
+            continue;
+        };
+        let include_children = has_property(query, query_match.pattern_index, INCLUDE_CHILDREN);
+        let ranges = content_ranges(content, include_children);
+        if has_property(query, query_match.pattern_index, COMBINED) {
+            combined
+                .entry((query_match.pattern_index, language))
+                .or_default()
+                .extend(ranges);
+        } else {
+            injections.push(Injection { language, ranges });
+        }
+    }
+    injections.extend(
+        combined
+            .into_iter()
+            .map(|((_, language), ranges)| Injection { language, ranges }),
+    );
+    injections.retain_mut(|injection| {
+        injection.ranges.sort_by_key(|range| range.start_byte);
+        injection
+            .ranges
+            .dedup_by(|range, previous| range.start_byte < previous.end_byte);
+        !injection.ranges.is_empty()
+    });
+    injections
+}
+
+/// The language of the innermost injection containing `byte`, or `language`
+/// if there isn't one.
+pub fn language_at(
+    source: &str,
+    tree: &Tree,
+    language: SupportedLanguage,
+    byte: usize,
+) -> SupportedLanguage {
+    let mut tree = tree.clone();
+    let mut language = language;
+    for _ in 0..MAX_DEPTH {
+        let Some((injection, injected_tree)) = find(source, &tree, language)
+            .into_iter()
+            .filter(|injection| {
+                injection
+                    .ranges
+                    .iter()
+                    .any(|range| range.start_byte <= byte && byte < range.end_byte)
+            })
+            .find_map(|injection| {
+                let injected_tree = injection.parse(source)?;
+                Some((injection, injected_tree))
+            })
+        else {
+            break;
+        };
+        language = injection.language;
+        tree = injected_tree;
+    }
+    language
+}
+
+/// `language` and the languages that can be embedded in it, transitively.
+pub fn reachable_languages(language: SupportedLanguage) -> Vec<SupportedLanguage> {
+    let mut languages = vec![language];
+    let mut index = 0;
+    while let Some(&language) = languages.get(index) {
+        for &embedded in language.embedded_languages() {
+            if !languages.contains(&embedded) {
+                languages.push(embedded);
+            }
+        }
+        index += 1;
+    }
+    languages
+}
+
+fn match_language_and_content<'tree>(
+    query: &Query,
+    query_match: &QueryMatch<'_, 'tree>,
+    source: &str,
+) -> Option<(SupportedLanguage, Node<'tree>)> {
+    let capture_names = query.capture_names();
+    let mut language_name = query
+        .property_settings(query_match.pattern_index)
+        .iter()
+        .find(|property| &*property.key == LANGUAGE)
+        .and_then(|property| property.value.as_deref());
+    let mut content = None;
+    for capture in query_match.captures {
+        match capture_names[capture.index as usize] {
+            CONTENT => content = Some(capture.node),
+            LANGUAGE => language_name = source.get(capture.node.byte_range()),
+            _ => {}
+        }
+    }
+    Some((SupportedLanguage::from_name(language_name?)?, content?))
+}
+
+fn has_property(query: &Query, pattern_index: usize, key: &str) -> bool {
+    query
+        .property_settings(pattern_index)
+        .iter()
+        .any(|property| &*property.key == key)
+}
+
+/// `node`'s range, minus those of its children unless `include_children`.
+fn content_ranges(node: Node, include_children: bool) -> Vec<Range> {
+    let mut ranges = vec![];
+    let mut start_byte = node.start_byte();
+    let mut start_point = node.start_position();
+    if !include_children {
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            if child.start_byte() > start_byte {
+                ranges.push(Range {
+                    start_byte,
+                    end_byte: child.start_byte(),
+                    start_point,
+                    end_point: child.start_position(),
+                });
+            }
+            start_byte = child.end_byte();
+            start_point = child.end_position();
+        }
+    }
+    if node.end_byte() > start_byte {
+        ranges.push(Range {
+            start_byte,
+            end_byte: node.end_byte(),
+            start_point,
+            end_point: node.end_position(),
+        });
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lint::{self, Linter},
+        plugins,
+    };
+
+    fn texts<'a>(source: &'a str, ranges: &[Range]) -> Vec<&'a str> {
+        ranges
+            .iter()
+            .map(|range| &source[range.start_byte..range.end_byte])
+            .collect()
+    }
+
+    #[test]
+    fn test_find_in_html() {
+        let source =
+            "<p>x</p>\n<script>\n  debugger;\n</script>\n<style>p { color: red }</style>\n";
+        let tree = lint::parse(source, SupportedLanguage::Html).unwrap();
+        let mut injections = find(source, &tree, SupportedLanguage::Html);
+        injections.sort_by_key(|injection| injection.ranges[0].start_byte);
+        assert_eq!(
+            injections
+                .iter()
+                .map(|injection| (injection.language, texts(source, &injection.ranges)))
+                .collect::<Vec<_>>(),
+            vec![
+                (SupportedLanguage::Javascript, vec!["\n  debugger;\n"]),
+                (SupportedLanguage::Css, vec!["p { color: red }"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_injected_trees_are_in_host_coordinates() {
+        let source = "<p>x</p>\n<script>\n  debugger;\n</script>\n";
+        let tree = lint::parse(source, SupportedLanguage::Html).unwrap();
+        let injection = find(source, &tree, SupportedLanguage::Html).remove(0);
+        let injected_tree = injection.parse(source).unwrap();
+        let statement = injected_tree.root_node().named_child(0).unwrap();
+        assert_eq!(statement.kind(), "debugger_statement");
+        assert_eq!(statement.start_byte(), source.find("debugger").unwrap());
+        assert_eq!(
+            statement.start_position(),
+            tree_sitter::Point { row: 2, column: 2 }
+        );
+    }
+
+    #[test]
+    fn test_macro_bodies_are_not_linted() {
+        let source =
+            "fn f() {\n    json!({ \"a\": v.len() == 0 });\n    quote! { let y = dbg!(#x); };\n}\n";
+        let tree = lint::parse(source, SupportedLanguage::Rust).unwrap();
+        assert!(find(source, &tree, SupportedLanguage::Rust).is_empty());
+
+        let linter = Linter::new(plugins::all());
+        assert!(linter
+            .lint(source, &tree, SupportedLanguage::Rust, &Default::default())
+            .is_empty());
+        assert_eq!(
+            linter.fix_all(source, SupportedLanguage::Rust, &Default::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_content_ranges() {
+        let source = "fn f() { (1, 2); }";
+        let tree = lint::parse(source, SupportedLanguage::Rust).unwrap();
+        let tuple = tree.root_node().descendant_for_byte_range(9, 15).unwrap();
+        assert_eq!(tuple.kind(), "tuple_expression");
+        assert_eq!(texts(source, &content_ranges(tuple, true)), vec!["(1, 2)"]);
+        assert_eq!(texts(source, &content_ranges(tuple, false)), vec![" "]);
+    }
+}
@@ -0,0 +1,226 @@
+use std::{fmt, path::Path};
+
+use serde::{Deserialize, Serialize};
+use tree_sitter::Language;
+
+use crate::directives::CommentSyntax;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SupportedLanguage {
+    Css,
+    Html,
+    #[serde(alias = "js")]
+    Javascript,
+    Python,
+    Rust,
+    #[serde(alias = "ts")]
+    Typescript,
+    Tsx,
+    #[serde(alias = "yml")]
+    Yaml,
+}
+
+impl SupportedLanguage {
+    pub const ALL: [Self; 8] = [
+        Self::Css,
+        Self::Html,
+        Self::Javascript,
+        Self::Python,
+        Self::Rust,
+        Self::Typescript,
+        Self::Tsx,
+        Self::Yaml,
+    ];
+
+    pub fn language(self) -> Language {
+        match self {
+            Self::Css => tree_sitter_css::LANGUAGE.into(),
+            Self::Html => tree_sitter_html::LANGUAGE.into(),
+            Self::Javascript => tree_sitter_javascript::LANGUAGE.into(),
+            Self::Python => tree_sitter_python::LANGUAGE.into(),
+            Self::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Self::Typescript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Self::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Self::Yaml => tree_sitter_yaml::LANGUAGE.into(),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Css => "css",
+            Self::Html => "html",
+            Self::Javascript => "javascript",
+            Self::Python => "python",
+            Self::Rust => "rust",
+            Self::Typescript => "typescript",
+            Self::Tsx => "tsx",
+            Self::Yaml => "yaml",
+        }
+    }
+
+    /// The tree-sitter injection query finding other languages embedded in
+    /// this one, if it has one.
+    pub fn injections_query(self) -> Option<&'static str> {
+        match self {
+            Self::Html => Some(tree_sitter_html::INJECTIONS_QUERY),
+            Self::Javascript | Self::Typescript | Self::Tsx => {
+                Some(tree_sitter_javascript::INJECTIONS_QUERY)
+            }
+            Self::Css | Self::Python | Self::Rust | Self::Yaml => None,
+        }
+    }
+
+    /// The languages `injections_query()` can find (that we support).
+    pub fn embedded_languages(self) -> &'static [Self] {
+        match self {
+            Self::Html => &[Self::Javascript, Self::Css],
+            Self::Javascript | Self::Typescript | Self::Tsx => &[Self::Css, Self::Html],
+            Self::Css | Self::Python | Self::Rust | Self::Yaml => &[],
+        }
+    }
+
+    pub fn comment_syntax(self) -> CommentSyntax {
+        match self {
+            Self::Css => CommentSyntax {
+                start: "/*",
+                end: "*/",
+            },
+            Self::Html => CommentSyntax {
+                start: "<!--",
+                end: "-->",
+            },
+            Self::Python | Self::Yaml => CommentSyntax {
+                start: "#",
+                end: "",
+            },
+            Self::Javascript | Self::Rust | Self::Typescript | Self::Tsx => CommentSyntax {
+                start: "//",
+                end: "",
+            },
+        }
+    }
+
+    /// Works out a document's language from (in order of preference) the
+    /// client's `languageId`, `path`'s extension, or a shebang line.
+    pub fn detect(language_id: Option<&str>, path: Option<&Path>, text: &str) -> Option<Self> {
+        language_id
+            .and_then(Self::from_language_id)
+            .or_else(|| path.and_then(Self::from_path))
+            .or_else(|| Self::from_shebang(text))
+    }
+
+    pub fn from_language_id(language_id: &str) -> Option<Self> {
+        match language_id {
+            "css" => Some(Self::Css),
+            "html" => Some(Self::Html),
+            "javascript" | "javascriptreact" => Some(Self::Javascript),
+            "python" => Some(Self::Python),
+            "rust" => Some(Self::Rust),
+            "typescript" => Some(Self::Typescript),
+            "typescriptreact" => Some(Self::Tsx),
+            "yaml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Resolves a language named by an injection query.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "js" | "jsx" => Some(Self::Javascript),
+            "ts" => Some(Self::Typescript),
+            "py" => Some(Self::Python),
+            "tsx" => Some(Self::Tsx),
+            "yml" => Some(Self::Yaml),
+            name => Self::from_language_id(name),
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "css" => Some(Self::Css),
+            "html" | "htm" => Some(Self::Html),
+            "js" | "mjs" | "cjs" | "jsx" => Some(Self::Javascript),
+            "py" | "pyi" => Some(Self::Python),
+            "rs" => Some(Self::Rust),
+            "ts" | "mts" | "cts" => Some(Self::Typescript),
+            "tsx" => Some(Self::Tsx),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Recognizes eg `#!/usr/bin/env python3` or `#!/usr/local/bin/node`.
+    pub fn from_shebang(text: &str) -> Option<Self> {
+        let mut words = text.lines().next()?.strip_prefix("#!")?.split_whitespace();
+        let mut interpreter = words.next()?.rsplit('/').next()?;
+        if interpreter == "env" {
+            interpreter = words.find(|word| !word.starts_with('-'))?;
+        }
+        match interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.') {
+            "node" | "nodejs" => Some(Self::Javascript),
+            "python" => Some(Self::Python),
+            "deno" | "ts-node" | "tsx" => Some(Self::Typescript),
+            "rust-script" => Some(Self::Rust),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SupportedLanguage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_precedence() {
+        let path = Path::new("/p/script.py");
+        let shebang = "#!/usr/bin/env node\n";
+        assert_eq!(
+            SupportedLanguage::detect(Some("rust"), Some(path), shebang),
+            Some(SupportedLanguage::Rust)
+        );
+        assert_eq!(
+            SupportedLanguage::detect(Some("plaintext"), Some(path), shebang),
+            Some(SupportedLanguage::Python)
+        );
+        assert_eq!(
+            SupportedLanguage::detect(None, Some(Path::new("/p/script")), shebang),
+            Some(SupportedLanguage::Javascript)
+        );
+        assert_eq!(
+            SupportedLanguage::detect(None, Some(Path::new("/p/script")), "x = 1\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_from_shebang() {
+        for (shebang, language) in [
+            ("#!/usr/bin/env python3", Some(SupportedLanguage::Python)),
+            ("#!/usr/bin/python3.11", Some(SupportedLanguage::Python)),
+            ("#!/usr/local/bin/node", Some(SupportedLanguage::Javascript)),
+            ("#! /usr/bin/env node", Some(SupportedLanguage::Javascript)),
+            (
+                "#!/usr/bin/env -S deno run --allow-read",
+                Some(SupportedLanguage::Typescript),
+            ),
+            ("#!/usr/bin/env rust-script", Some(SupportedLanguage::Rust)),
+            ("#!/bin/sh", None),
+            ("#!/usr/bin/env -S", None),
+            ("# not a shebang", None),
+            ("#![allow(dead_code)]", None),
+        ] {
+            assert_eq!(
+                SupportedLanguage::from_shebang(&format!("{shebang}\nbody\n")),
+                language,
+                "{shebang}"
+            );
+        }
+    }
+}
@@ -0,0 +1,27 @@
+pub mod backend;
+pub mod code_actions;
+pub mod commands;
+pub mod config;
+pub mod config_document;
+pub mod diagnostics;
+pub mod directives;
+pub mod document;
+pub mod fixer;
+pub mod hover;
+pub mod injections;
+pub mod language;
+pub mod lint;
+pub mod local_binary;
+pub mod plugins;
+pub mod position;
+pub mod progress;
+pub mod rule;
+pub mod rule_definitions;
+pub mod rule_queries;
+pub mod scheduler;
+pub mod schema;
+pub mod settings;
+pub mod violation;
+pub mod workspace;
+
+pub use backend::Backend;
@@ -0,0 +1,328 @@
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{Parser, Query, QueryCursor, Tree};
+
+use crate::{
+    config::Config,
+    directives, injections,
+    language::SupportedLanguage,
+    rule::{Plugin, QueryMatchContext, RuleMeta},
+    rule_queries,
+    scheduler::CancellationToken,
+    violation::{Edit, Violation},
+};
+
+const MAX_FIX_ITERATIONS: usize = 10;
+
+struct CompiledListener {
+    plugin_index: usize,
+    rule_index: usize,
+    listener_index: usize,
+    language: SupportedLanguage,
+    query: Query,
+}
+
+pub struct Linter {
+    plugins: Vec<Plugin>,
+    listeners: Vec<CompiledListener>,
+}
+
+impl Linter {
+    pub fn new(plugins: Vec<Plugin>) -> Self {
+        let mut listeners = vec![];
+        for (plugin_index, plugin) in plugins.iter().enumerate() {
+            for (rule_index, rule) in plugin.rules.iter().enumerate() {
+                for &language in &rule.languages {
+                    for (listener_index, listener) in rule.listeners.iter().enumerate() {
+                        let query = Query::new(&language.language(), listener.query)
+                            .unwrap_or_else(|error| {
+                                panic!(
+                                    "invalid query for rule {}/{}: {error}",
+                                    plugin.name, rule.name
+                                )
+                            });
+                        listeners.push(CompiledListener {
+                            plugin_index,
+                            rule_index,
+                            listener_index,
+                            language,
+                            query,
+                        });
+                    }
+                }
+            }
+        }
+
+        Self { plugins, listeners }
+    }
+
+    pub fn rules(&self) -> Vec<RuleMeta> {
+        self.plugins
+            .iter()
+            .flat_map(|plugin| {
+                plugin
+                    .rules
+                    .iter()
+                    .map(move |rule| RuleMeta::new(plugin, rule))
+            })
+            .collect()
+    }
+
+    /// Whether `name` (`plugin/rule`) is one of our rules.
+    pub fn has_rule(&self, name: &str) -> bool {
+        self.plugins.iter().any(|plugin| {
+            plugin
+                .rules
+                .iter()
+                .any(|rule| directives::is_rule(name, plugin.name, rule.name))
+        })
+    }
+
+    pub fn lint(
+        &self,
+        source: &str,
+        tree: &Tree,
+        language: SupportedLanguage,
+        config: &Config,
+    ) -> Vec<Violation> {
+        self.lint_cancellable(source, tree, language, config, &Default::default())
+            .unwrap()
+    }
+
+    /// Like `lint()`, but gives up (returning `None`) as soon as
+    /// `cancellation` is observed between listeners. Problems with the
+    /// queries of any rules defined in (Rust) `source` are reported too.
+    pub fn lint_cancellable(
+        &self,
+        source: &str,
+        tree: &Tree,
+        language: SupportedLanguage,
+        config: &Config,
+        cancellation: &CancellationToken,
+    ) -> Option<Vec<Violation>> {
+        let violations = self.lint_tree(source, tree, language, config, cancellation, 0)?;
+        Some(finish_violations(
+            source,
+            tree,
+            language,
+            violations,
+            |name| self.has_rule(name),
+        ))
+    }
+
+    /// Runs the listeners for `language` over `tree`, then does the same for
+    /// any languages injected into it.
+    fn lint_tree(
+        &self,
+        source: &str,
+        tree: &Tree,
+        language: SupportedLanguage,
+        config: &Config,
+        cancellation: &CancellationToken,
+        depth: usize,
+    ) -> Option<Vec<Violation>> {
+        let mut violations = vec![];
+        let mut query_cursor = QueryCursor::new();
+        for listener in self
+            .listeners
+            .iter()
+            .filter(|listener| listener.language == language)
+        {
+            if cancellation.is_cancelled() {
+                return None;
+            }
+            let plugin = &self.plugins[listener.plugin_index];
+            let rule = &plugin.rules[listener.rule_index];
+            let Some((level, options)) = config.resolve_rule(plugin.name, rule) else {
+                continue;
+            };
+            let on_match = rule.listeners[listener.listener_index].on_match;
+            let capture_names = listener.query.capture_names();
+            let mut context =
+                QueryMatchContext::new(source, plugin, rule, level, options, &mut violations);
+            let mut matches =
+                query_cursor.matches(&listener.query, tree.root_node(), source.as_bytes());
+            while let Some(query_match) = matches.next() {
+                for capture in query_match.captures {
+                    if capture_names[capture.index as usize].starts_with('_') {
+                        continue;
+                    }
+                    on_match(capture.node, &mut context);
+                }
+            }
+        }
+
+        if depth == injections::MAX_DEPTH {
+            return Some(violations);
+        }
+        for injection in injections::find(source, tree, language) {
+            if !self.lints_any_of(&injections::reachable_languages(injection.language)) {
+                continue;
+            }
+            let Some(injected_tree) = injection.parse(source) else {
+                continue;
+            };
+            violations.extend(self.lint_tree(
+                source,
+                &injected_tree,
+                injection.language,
+                config,
+                cancellation,
+                depth + 1,
+            )?);
+        }
+        Some(violations)
+    }
+
+    fn lints_any_of(&self, languages: &[SupportedLanguage]) -> bool {
+        self.listeners
+            .iter()
+            .any(|listener| languages.contains(&listener.language))
+    }
+
+    pub fn fix_all(
+        &self,
+        source: &str,
+        language: SupportedLanguage,
+        config: &Config,
+    ) -> Option<String> {
+        fix_all_with(source, |source| {
+            let tree = parse(source, language)?;
+            Some(self.lint(source, &tree, language, config))
+        })
+    }
+}
+
+/// Adds any problems with rule queries to `violations` (of `source`, parsed
+/// as `tree`), then applies `source`'s directives. `is_known_rule` is as for
+/// `directives::apply()`.
+pub fn finish_violations(
+    source: &str,
+    tree: &Tree,
+    language: SupportedLanguage,
+    mut violations: Vec<Violation>,
+    is_known_rule: impl Fn(&str) -> bool,
+) -> Vec<Violation> {
+    if language == SupportedLanguage::Rust {
+        violations.extend(rule_queries::check(source, tree));
+    }
+    violations.sort_by_key(|violation| violation.range.start_byte);
+    directives::apply(source, tree, language, violations, is_known_rule)
+}
+
+/// Repeatedly lints (using `lint`) and applies every non-overlapping fix until
+/// no fixable violations remain (or `MAX_FIX_ITERATIONS` is hit). Returns
+/// `None` if nothing was fixed.
+pub fn fix_all_with(
+    source: &str,
+    mut lint: impl FnMut(&str) -> Option<Vec<Violation>>,
+) -> Option<String> {
+    let mut fixed = source.to_owned();
+    for _ in 0..MAX_FIX_ITERATIONS {
+        let Some(violations) = lint(&fixed) else {
+            break;
+        };
+        match apply_fixes(&fixed, &violations) {
+            Some(next) => fixed = next,
+            None => break,
+        }
+    }
+    (fixed != source).then_some(fixed)
+}
+
+fn apply_fixes(source: &str, violations: &[Violation]) -> Option<String> {
+    let mut fixes = violations
+        .iter()
+        .filter_map(|violation| {
+            let fix = violation.fix.as_ref()?;
+            let start_byte = fix.iter().map(|edit| edit.range.start_byte).min()?;
+            let end_byte = fix.iter().map(|edit| edit.range.end_byte).max()?;
+            Some((start_byte, end_byte, fix))
+        })
+        .collect::<Vec<_>>();
+    fixes.sort_by_key(|&(start_byte, _, _)| start_byte);
+
+    let mut accepted: Vec<&Edit> = vec![];
+    let mut last_end_byte = None;
+    for (start_byte, end_byte, fix) in fixes {
+        if last_end_byte.is_some_and(|last_end_byte| start_byte < last_end_byte) {
+            continue;
+        }
+        accepted.extend(fix);
+        last_end_byte = Some(end_byte);
+    }
+    if accepted.is_empty() {
+        return None;
+    }
+
+    accepted.sort_by_key(|edit| edit.range.start_byte);
+    let mut fixed = source.to_owned();
+    for edit in accepted.into_iter().rev() {
+        fixed.replace_range(
+            edit.range.start_byte..edit.range.end_byte,
+            &edit.replacement,
+        );
+    }
+    Some(fixed)
+}
+
+pub fn parse(source: &str, language: SupportedLanguage) -> Option<Tree> {
+    let mut parser = Parser::new();
+    parser.set_language(&language.language()).ok()?;
+    parser.parse(source, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::violation::{byte_range_to_range, RuleLevel};
+
+    fn fixable(source: &str, range: std::ops::Range<usize>, replacement: &str) -> Violation {
+        Violation {
+            fix: Some(vec![Edit {
+                range: byte_range_to_range(source, range.clone()),
+                replacement: replacement.to_owned(),
+            }]),
+            ..Violation::internal(source, "test", RuleLevel::Warning, String::new(), range)
+        }
+    }
+
+    /// Reports every `aa` (overlapping ones too), fixed by replacing it with
+    /// `a`.
+    fn lint_double_a(source: &str) -> Option<Vec<Violation>> {
+        Some(
+            (0..source.len().saturating_sub(1))
+                .filter(|&index| &source[index..index + 2] == "aa")
+                .map(|index| fixable(source, index..index + 2, "a"))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_overlapping_fixes_are_skipped() {
+        let source = "aaaa b";
+        assert_eq!(
+            apply_fixes(source, &lint_double_a(source).unwrap()).as_deref(),
+            Some("aa b")
+        );
+    }
+
+    #[test]
+    fn test_fix_all_relints_until_nothing_is_fixable() {
+        assert_eq!(
+            fix_all_with("aaaa b aa", lint_double_a).as_deref(),
+            Some("a b a")
+        );
+        assert_eq!(fix_all_with("a b", lint_double_a), None);
+    }
+
+    #[test]
+    fn test_fix_all_gives_up_after_max_iterations() {
+        let mut lints = 0;
+        let fixed = fix_all_with("x", |source| {
+            lints += 1;
+            Some(vec![fixable(source, source.len()..source.len(), "x")])
+        });
+        assert_eq!(lints, MAX_FIX_ITERATIONS);
+        assert_eq!(fixed, Some("x".repeat(MAX_FIX_ITERATIONS + 1)));
+    }
+}
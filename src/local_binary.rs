@@ -0,0 +1,311 @@
+//! Delegates linting to a project's `tree-sitter-lint-local` binary, which
+//! has the project's custom rules compiled into it.
+//!
+//! The binary is spawned as `tree-sitter-lint-local serve` and spoken to over
+//! its stdin/stdout, one JSON object per line. Each request looks like
+//! `{"id": 1, "method": "lint", "params": {"path": ..., "source": ...}}` and is
+//! answered by either `{"id": 1, "result": ...}` or `{"id": 1, "error": "..."}`.
+//! The supported methods are `lint` (answered with `{"violations": [...]}`)
+//! and `rules` (answered with `{"rules": [...]}`).
+//!
+//! A binary that takes longer than `RESPONSE_TIMEOUT` to answer is killed
+//! (and respawned next time), and one that can't be spawned isn't tried again
+//! for `SPAWN_RETRY_DELAY`.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::{rule::RuleMeta, violation::Violation};
+
+pub const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+pub const SPAWN_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error)]
+pub enum LocalBinaryError {
+    #[error("couldn't spawn {}: {source}", binary.display())]
+    Spawn { binary: PathBuf, source: io::Error },
+    #[error("lost connection to local binary: {0}")]
+    Io(#[from] io::Error),
+    #[error("local binary exited unexpectedly")]
+    Exited,
+    #[error("local binary didn't respond within {0:?}")]
+    TimedOut(Duration),
+    /// Spawning the binary failed recently, so it wasn't tried again.
+    #[error("{} is unavailable", binary.display())]
+    Unavailable { binary: PathBuf },
+    #[error("invalid response from local binary: {0}")]
+    InvalidResponse(#[from] serde_json::Error),
+    #[error("local binary failed: {0}")]
+    Failed(String),
+}
+
+#[derive(Serialize)]
+#[serde(tag = "method", content = "params", rename_all = "camelCase")]
+enum Method<'a> {
+    Lint { path: &'a Path, source: &'a str },
+    Rules,
+}
+
+#[derive(Serialize)]
+struct Request<'a> {
+    id: u64,
+    #[serde(flatten)]
+    method: Method<'a>,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    id: u64,
+    #[serde(default)]
+    result: Value,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LintResult {
+    violations: Vec<Violation>,
+}
+
+#[derive(Deserialize)]
+struct RulesResult {
+    rules: Vec<RuleMeta>,
+}
+
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines of stdout, read on another thread so that waiting for them can
+    /// time out.
+    lines: Receiver<io::Result<String>>,
+}
+
+impl Process {
+    fn spawn(binary: &Path) -> Result<Self, LocalBinaryError> {
+        let mut child = Command::new(binary)
+            .arg("serve")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|source| LocalBinaryError::Spawn {
+                binary: binary.to_owned(),
+                source,
+            })?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in stdout.lines() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self {
+            child,
+            stdin,
+            lines,
+        })
+    }
+
+    fn request(
+        &mut self,
+        id: u64,
+        method: Method,
+        timeout: Duration,
+    ) -> Result<Value, LocalBinaryError> {
+        let mut request = serde_json::to_vec(&Request { id, method })?;
+        request.push(b'\n');
+        self.stdin.write_all(&request)?;
+        self.stdin.flush()?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let line = match self
+                .lines
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            {
+                Ok(line) => line?,
+                Err(RecvTimeoutError::Timeout) => return Err(LocalBinaryError::TimedOut(timeout)),
+                Err(RecvTimeoutError::Disconnected) => return Err(LocalBinaryError::Exited),
+            };
+            let response: Response = serde_json::from_str(&line)?;
+            if response.id != id {
+                continue;
+            }
+            return match response.error {
+                Some(error) => Err(LocalBinaryError::Failed(error)),
+                None => Ok(response.result),
+            };
+        }
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A lazily-spawned connection to a local binary. If the process dies (or
+/// hangs) it gets respawned on the next request.
+pub struct LocalBinary {
+    binary: PathBuf,
+    /// `RESPONSE_TIMEOUT`, except in tests.
+    response_timeout: Duration,
+    process: Mutex<Option<Process>>,
+    /// When spawning the binary last failed.
+    spawn_failed_at: Mutex<Option<Instant>>,
+    next_id: AtomicU64,
+    rules: Mutex<Option<Arc<Vec<RuleMeta>>>>,
+}
+
+impl LocalBinary {
+    pub fn new(binary: PathBuf) -> Self {
+        Self {
+            binary,
+            response_timeout: RESPONSE_TIMEOUT,
+            process: Default::default(),
+            spawn_failed_at: Default::default(),
+            next_id: Default::default(),
+            rules: Default::default(),
+        }
+    }
+
+    pub fn lint(&self, path: &Path, source: &str) -> Result<Vec<Violation>, LocalBinaryError> {
+        let result: LintResult = self.request(Method::Lint { path, source })?;
+        Ok(result.violations)
+    }
+
+    /// The metadata of every rule the binary knows about, fetched once.
+    pub fn rules(&self) -> Result<Arc<Vec<RuleMeta>>, LocalBinaryError> {
+        if let Some(rules) = self.rules.lock().unwrap().as_ref() {
+            return Ok(rules.clone());
+        }
+        let result: RulesResult = self.request(Method::Rules)?;
+        let rules = Arc::new(result.rules);
+        *self.rules.lock().unwrap() = Some(rules.clone());
+        Ok(rules)
+    }
+
+    fn request<T: DeserializeOwned>(&self, method: Method) -> Result<T, LocalBinaryError> {
+        let mut process = self.process.lock().unwrap();
+        if process.is_none() {
+            *process = Some(self.spawn()?);
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let result = process
+            .as_mut()
+            .unwrap()
+            .request(id, method, self.response_timeout);
+        if matches!(
+            result,
+            Err(LocalBinaryError::Io(_) | LocalBinaryError::Exited | LocalBinaryError::TimedOut(_))
+        ) {
+            *process = None;
+        }
+        Ok(serde_json::from_value(result?)?)
+    }
+
+    fn spawn(&self) -> Result<Process, LocalBinaryError> {
+        let mut spawn_failed_at = self.spawn_failed_at.lock().unwrap();
+        if spawn_failed_at.is_some_and(|failed_at| failed_at.elapsed() < SPAWN_RETRY_DELAY) {
+            return Err(LocalBinaryError::Unavailable {
+                binary: self.binary.clone(),
+            });
+        }
+        let process = Process::spawn(&self.binary);
+        *spawn_failed_at = process.is_err().then(Instant::now);
+        process
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, os::unix::fs::PermissionsExt};
+
+    use super::*;
+
+    /// Writes `script` (the body of a shell script) to a new executable file.
+    fn script(name: &str, script: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("local-binary-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{script}")).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn local_binary(binary: PathBuf) -> LocalBinary {
+        LocalBinary {
+            response_timeout: Duration::from_millis(500),
+            ..LocalBinary::new(binary)
+        }
+    }
+
+    #[test]
+    fn test_answering_binary() {
+        let binary = local_binary(script(
+            "answers",
+            r#"while read -r line; do
+  id=$(echo "$line" | sed 's/^{"id":\([0-9]*\).*/\1/')
+  case "$line" in
+    *'"method":"rules"'*) echo "{\"id\":$id,\"result\":{\"rules\":[{\"plugin\":\"local\",\"name\":\"custom\",\"description\":\"d\"}]}}" ;;
+    *) echo "{\"id\":$id,\"result\":{\"violations\":[]}}" ;;
+  esac
+done
+"#,
+        ));
+        assert!(binary
+            .lint(Path::new("a.rs"), "fn f() {}")
+            .unwrap()
+            .is_empty());
+        let rules = binary.rules().unwrap();
+        assert_eq!(
+            rules
+                .iter()
+                .map(|meta| format!("{}/{}", meta.plugin, meta.name))
+                .collect::<Vec<_>>(),
+            vec!["local/custom"]
+        );
+    }
+
+    #[test]
+    fn test_hanging_binary_times_out() {
+        let binary = local_binary(script("hangs", "exec sleep 1000\n"));
+        assert!(matches!(
+            binary.lint(Path::new("a.rs"), ""),
+            Err(LocalBinaryError::TimedOut(_))
+        ));
+        // Killed, to be respawned next time.
+        assert!(binary.process.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_missing_binary_is_not_respawned_right_away() {
+        let binary = local_binary(env::temp_dir().join("no-such-local-binary"));
+        assert!(matches!(
+            binary.lint(Path::new("a.rs"), ""),
+            Err(LocalBinaryError::Spawn { .. })
+        ));
+        assert!(matches!(
+            binary.rules(),
+            Err(LocalBinaryError::Unavailable { .. })
+        ));
+    }
+}
@@ -1,3 +1,11 @@
-fn main() {
-    println!("Hello, world!");
+use tower_lsp::{LspService, Server};
+use tree_sitter_lint_lsp::Backend;
+
+#[tokio::main]
+async fn main() {
+    let stdin = tokio::io::stdin();
+    let stdout = tokio::io::stdout();
+
+    let (service, socket) = LspService::new(Backend::new);
+    Server::new(stdin, stdout, socket).serve(service).await;
 }
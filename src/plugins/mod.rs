@@ -0,0 +1,7 @@
+use crate::rule::Plugin;
+
+mod rust;
+
+pub fn all() -> Vec<Plugin> {
+    vec![rust::plugin()]
+}
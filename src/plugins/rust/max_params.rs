@@ -0,0 +1,52 @@
+use serde_json::json;
+
+use crate::{rule, rule::Rule};
+
+const DEFAULT_MAX: u64 = 7;
+
+pub fn max_params_rule() -> Rule {
+    rule! {
+        name => "max-params",
+        description => "Enforce a maximum number of parameters in function definitions",
+        docs => r#"Functions that take many parameters are hard to call correctly. Consider grouping related parameters into a struct.
+
+The maximum can be configured with the `max` option (default `7`)."#,
+        level => Warning,
+        options_schema => json!({
+            "type": "object",
+            "properties": {
+                "max": {
+                    "type": "integer",
+                    "minimum": 0,
+                    "description": "The maximum number of parameters allowed.",
+                    "default": DEFAULT_MAX,
+                },
+            },
+            "additionalProperties": false,
+        }),
+        languages => [Rust],
+        listeners => [
+            r#"(parameters) @parameters"# => |node, context| {
+                let max = context
+                    .options()
+                    .get("max")
+                    .and_then(|max| max.as_u64())
+                    .unwrap_or(DEFAULT_MAX);
+                let parameters = node.named_children(&mut node.walk())
+                    .filter(|child| matches!(child.kind(), "parameter" | "self_parameter"))
+                    .collect::<Vec<_>>();
+                let count = parameters.len() as u64;
+                if count > max {
+                    context.report_with_related(
+                        node,
+                        format!("Function has too many parameters ({count}). Maximum allowed is {max}."),
+                        parameters
+                            .into_iter()
+                            .skip(max as usize)
+                            .map(|parameter| (parameter, "Parameter over the maximum.".to_owned())),
+                    );
+                }
+            },
+        ],
+    }
+}
@@ -0,0 +1,20 @@
+use crate::rule::Plugin;
+
+mod max_params;
+mod no_dbg_macro;
+mod no_todo_macro;
+mod no_unit_return_type;
+mod prefer_is_empty;
+
+pub fn plugin() -> Plugin {
+    Plugin {
+        name: "rust",
+        rules: vec![
+            max_params::max_params_rule(),
+            no_dbg_macro::no_dbg_macro_rule(),
+            no_todo_macro::no_todo_macro_rule(),
+            no_unit_return_type::no_unit_return_type_rule(),
+            prefer_is_empty::prefer_is_empty_rule(),
+        ],
+    }
+}
@@ -0,0 +1,44 @@
+use crate::{rule, rule::Rule};
+
+pub fn no_dbg_macro_rule() -> Rule {
+    rule! {
+        name => "no-dbg-macro",
+        description => "Disallow leftover `dbg!()` invocations",
+        docs => r#"`dbg!()` is meant for temporary debugging and shouldn't be committed.
+
+```rust
+let x = dbg!(compute()); // bad
+let x = compute(); // good
+```"#,
+        fixable => true,
+        languages => [Rust],
+        listeners => [
+            r#"(macro_invocation
+                macro: (identifier) @_name
+                (#eq? @_name "dbg")
+            ) @macro_invocation"# => |node, context| {
+                const MESSAGE: &str = "Unexpected `dbg!()` invocation.";
+
+                let token_tree = node.named_child(1).unwrap();
+                let has_single_argument = token_tree.named_child_count() > 0
+                    && !token_tree
+                        .children(&mut token_tree.walk())
+                        .any(|child| child.kind() == ",");
+                if !has_single_argument {
+                    context.report(node, MESSAGE);
+                    return;
+                }
+                let (Some(open), Some(close)) = (
+                    token_tree.child(0),
+                    token_tree.child(token_tree.child_count() - 1),
+                ) else {
+                    return;
+                };
+                let argument = context.get_text(open.end_byte()..close.start_byte()).trim();
+                context.report_with_fix(node, MESSAGE, |fixer| {
+                    fixer.replace_text(node, argument);
+                });
+            },
+        ],
+    }
+}
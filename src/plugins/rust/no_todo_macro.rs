@@ -0,0 +1,20 @@
+use crate::{rule, rule::Rule};
+
+pub fn no_todo_macro_rule() -> Rule {
+    rule! {
+        name => "no-todo-macro",
+        description => "Disallow `todo!()` and `unimplemented!()` placeholders",
+        docs => r#"`todo!()` and `unimplemented!()` panic when reached, so they usually mark unfinished code."#,
+        level => Warning,
+        languages => [Rust],
+        listeners => [
+            r#"(macro_invocation
+                macro: (identifier) @_name
+                (#any-of? @_name "todo" "unimplemented")
+            ) @macro_invocation"# => |node, context| {
+                let name = context.get_node_text(node.child_by_field_name("macro").unwrap());
+                context.report(node, format!("Unexpected `{name}!()` placeholder."));
+            },
+        ],
+    }
+}
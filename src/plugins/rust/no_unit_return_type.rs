@@ -0,0 +1,39 @@
+use tree_sitter::Range;
+
+use crate::{rule, rule::Rule};
+
+pub fn no_unit_return_type_rule() -> Rule {
+    rule! {
+        name => "no-unit-return-type",
+        description => "Disallow explicitly returning `()` from functions",
+        docs => r#"Functions without a return type already return `()`.
+
+```rust
+fn foo() -> () {} // bad
+fn foo() {} // good
+```"#,
+        fixable => true,
+        tags => [Unnecessary],
+        languages => [Rust],
+        listeners => [
+            r#"
+              (function_item
+                return_type: (unit_type) @return_type
+              )
+              (function_signature_item
+                return_type: (unit_type) @return_type
+              )
+            "# => |node, context| {
+                let preceding = node.prev_sibling().and_then(|arrow| arrow.prev_sibling()).unwrap();
+                context.report_with_fix(node, "Unnecessary `-> ()` return type.", |fixer| {
+                    fixer.remove_range(Range {
+                        start_byte: preceding.end_byte(),
+                        end_byte: node.end_byte(),
+                        start_point: preceding.end_position(),
+                        end_point: node.end_position(),
+                    });
+                });
+            },
+        ],
+    }
+}
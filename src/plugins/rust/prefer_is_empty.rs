@@ -0,0 +1,51 @@
+use crate::{rule, rule::Rule};
+
+pub fn prefer_is_empty_rule() -> Rule {
+    rule! {
+        name => "prefer-is-empty",
+        description => "Prefer `.is_empty()` over comparing `.len()` to zero",
+        docs => r#"`.is_empty()` states the intent more directly and can be cheaper than computing the length.
+
+```rust
+if v.len() == 0 {} // bad
+if v.is_empty() {} // good
+```"#,
+        fixable => true,
+        languages => [Rust],
+        listeners => [
+            r#"(binary_expression
+                left: (call_expression
+                  function: (field_expression
+                    field: (field_identifier) @_method
+                  )
+                  arguments: (arguments) @_arguments
+                )
+                operator: ["==" "!="]
+                right: (integer_literal) @_zero
+                (#eq? @_method "len")
+                (#eq? @_zero "0")
+            ) @comparison"# => |node, context| {
+                let call = node.child_by_field_name("left").unwrap();
+                if call.child_by_field_name("arguments").unwrap().named_child_count() > 0 {
+                    return;
+                }
+                let receiver = context.get_node_text(
+                    call.child_by_field_name("function")
+                        .and_then(|function| function.child_by_field_name("value"))
+                        .unwrap(),
+                );
+                let negation = match node.child_by_field_name("operator").unwrap().kind() {
+                    "==" => "",
+                    _ => "!",
+                };
+                context.report_with_fix(
+                    node,
+                    "Use `.is_empty()` instead of comparing `.len()` to zero.",
+                    |fixer| {
+                        fixer.replace_text(node, format!("{negation}{receiver}.is_empty()"));
+                    },
+                );
+            },
+        ],
+    }
+}
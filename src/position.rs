@@ -0,0 +1,339 @@
+use ropey::{Rope, RopeSlice};
+use tower_lsp::lsp_types::{Position, PositionEncodingKind, Range};
+use tree_sitter::Point;
+
+/// What LSP `Position::character` counts, as negotiated with the client.
+/// (tree-sitter's columns are always in bytes.)
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PositionEncoding {
+    Utf8,
+    #[default]
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// Picks the first of the client's supported encodings (listed in order of
+    /// preference) that we support too, falling back to UTF-16 as every
+    /// client must support it.
+    pub fn negotiate(client_encodings: Option<&[PositionEncodingKind]>) -> Self {
+        client_encodings
+            .into_iter()
+            .flatten()
+            .find_map(|kind| match kind.as_str() {
+                "utf-8" => Some(Self::Utf8),
+                "utf-16" => Some(Self::Utf16),
+                "utf-32" => Some(Self::Utf32),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn kind(self) -> PositionEncodingKind {
+        match self {
+            Self::Utf8 => PositionEncodingKind::UTF8,
+            Self::Utf16 => PositionEncodingKind::UTF16,
+            Self::Utf32 => PositionEncodingKind::UTF32,
+        }
+    }
+
+    /// The length of `text` (which mustn't span lines) in this encoding.
+    fn len(self, text: &str) -> usize {
+        match self {
+            Self::Utf8 => text.len(),
+            Self::Utf16 => text.encode_utf16().count(),
+            Self::Utf32 => text.chars().count(),
+        }
+    }
+
+    /// Converts `character` (in this encoding) into a char index into `line`,
+    /// rounding down to the start of the char if it's in the middle of one.
+    fn char_index(self, line: RopeSlice, character: usize) -> usize {
+        match self {
+            Self::Utf8 => line.byte_to_char(character.min(line.len_bytes())),
+            Self::Utf16 => line.utf16_cu_to_char(character.min(line.len_utf16_cu())),
+            Self::Utf32 => character.min(line.len_chars()),
+        }
+    }
+}
+
+/// Converts a byte offset into `rope` into an LSP position, rounding down to
+/// the start of the char if it's in the middle of one.
+pub fn byte_to_position(rope: &Rope, byte: usize, encoding: PositionEncoding) -> Position {
+    let char_index = rope.byte_to_char(byte.min(rope.len_bytes()));
+    let line = rope.char_to_line(char_index);
+    let line_start = rope.line_to_char(line);
+    let character = match encoding {
+        PositionEncoding::Utf8 => rope.char_to_byte(char_index) - rope.line_to_byte(line),
+        PositionEncoding::Utf16 => {
+            rope.char_to_utf16_cu(char_index) - rope.char_to_utf16_cu(line_start)
+        }
+        PositionEncoding::Utf32 => char_index - line_start,
+    };
+    Position {
+        line: line as u32,
+        character: character as u32,
+    }
+}
+
+/// Converts a tree-sitter range into an LSP one, going by its bytes (as
+/// its rows only count `\n`s, while LSP lines end at a lone `\r` too).
+pub fn range_to_lsp(rope: &Rope, range: &tree_sitter::Range, encoding: PositionEncoding) -> Range {
+    Range {
+        start: byte_to_position(rope, range.start_byte, encoding),
+        end: byte_to_position(rope, range.end_byte, encoding),
+    }
+}
+
+pub fn ranges_overlap(a: &Range, b: &Range) -> bool {
+    a.start <= b.end && b.start <= a.end
+}
+
+pub fn document_end_position(text: &str, encoding: PositionEncoding) -> Position {
+    let line_ending =
+        |(index, ending): &(usize, &str)| *ending == "\n" || !text[index + 1..].starts_with('\n');
+    let line_endings = text.match_indices(['\n', '\r']).filter(line_ending);
+    let (line, last_line_start) = line_endings
+        .enumerate()
+        .last()
+        .map_or((0, 0), |(line, (index, _))| (line + 1, index + 1));
+    Position {
+        line: line as u32,
+        character: encoding.len(&text[last_line_start..]) as u32,
+    }
+}
+
+/// Converts an LSP position into a char index into `rope`, clamping positions
+/// past the end of a line (to before its line ending) or of the document.
+pub fn position_to_char_index(
+    rope: &Rope,
+    position: Position,
+    encoding: PositionEncoding,
+) -> usize {
+    let line = position.line as usize;
+    if line >= rope.len_lines() {
+        return rope.len_chars();
+    }
+    rope.line_to_char(line)
+        + encoding.char_index(
+            line_without_ending(rope.line(line)),
+            position.character as usize,
+        )
+}
+
+fn line_without_ending(line: RopeSlice) -> RopeSlice {
+    let mut len = line.len_chars();
+    if len > 0 && line.char(len - 1) == '\n' {
+        len -= 1;
+    }
+    if len > 0 && line.char(len - 1) == '\r' {
+        len -= 1;
+    }
+    line.slice(..len)
+}
+
+/// The tree-sitter point at `char_index`. Its row counts `\n`s only, so
+/// isn't necessarily `rope`'s line (which ends at a lone `\r` too, as LSP
+/// lines do).
+pub fn char_index_to_point(rope: &Rope, char_index: usize) -> Point {
+    let byte = rope.char_to_byte(char_index);
+    let mut row = 0;
+    let mut row_start = 0;
+    let mut chunk_start = 0;
+    for chunk in rope.byte_slice(..byte).chunks() {
+        for (index, _) in chunk.match_indices('\n') {
+            row += 1;
+            row_start = chunk_start + index + 1;
+        }
+        chunk_start += chunk.len();
+    }
+    Point {
+        row,
+        column: byte - row_start,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "let s = \"😀😀\"; dbg!(s);\n// 日本語 dbg!(x)\n";
+
+    fn point_of(text: &str, needle: &str) -> Point {
+        let byte = text.find(needle).unwrap();
+        let row = text[..byte].matches('\n').count();
+        let line_start = text[..byte].rfind('\n').map_or(0, |index| index + 1);
+        Point {
+            row,
+            column: byte - line_start,
+        }
+    }
+
+    fn position(line: u32, character: u32) -> Position {
+        Position { line, character }
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(PositionEncoding::negotiate(None), PositionEncoding::Utf16);
+        assert_eq!(
+            PositionEncoding::negotiate(Some(&[
+                PositionEncodingKind::new("utf-7"),
+                PositionEncodingKind::UTF32,
+                PositionEncodingKind::UTF8,
+            ])),
+            PositionEncoding::Utf32
+        );
+        assert_eq!(
+            PositionEncoding::negotiate(Some(&[])),
+            PositionEncoding::Utf16
+        );
+    }
+
+    #[test]
+    fn test_byte_to_position_after_emoji() {
+        let rope = Rope::from_str(TEXT);
+        let byte = TEXT.find("dbg").unwrap();
+        assert_eq!(byte, 20);
+        for (encoding, character) in [
+            (PositionEncoding::Utf8, 20),
+            (PositionEncoding::Utf16, 16),
+            (PositionEncoding::Utf32, 14),
+        ] {
+            assert_eq!(
+                byte_to_position(&rope, byte, encoding),
+                position(0, character),
+                "{encoding:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_byte_to_position_after_cjk() {
+        let rope = Rope::from_str(TEXT);
+        let byte = TEXT.find("dbg!(x)").unwrap();
+        assert_eq!(point_of(TEXT, "dbg!(x)").column, 13);
+        for (encoding, character) in [
+            (PositionEncoding::Utf8, 13),
+            (PositionEncoding::Utf16, 7),
+            (PositionEncoding::Utf32, 7),
+        ] {
+            assert_eq!(
+                byte_to_position(&rope, byte, encoding),
+                position(1, character),
+                "{encoding:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_position_round_trips() {
+        let rope = Rope::from_str(TEXT);
+        for encoding in [
+            PositionEncoding::Utf8,
+            PositionEncoding::Utf16,
+            PositionEncoding::Utf32,
+        ] {
+            for needle in ["😀😀", "😀\"", "dbg!(s)", "日本語", "本語", "dbg!(x)"] {
+                let byte = TEXT.find(needle).unwrap();
+                let char_index = position_to_char_index(
+                    &rope,
+                    byte_to_position(&rope, byte, encoding),
+                    encoding,
+                );
+                assert_eq!(rope.char_to_byte(char_index), byte, "{encoding:?} {needle}");
+                assert_eq!(
+                    char_index_to_point(&rope, char_index),
+                    point_of(TEXT, needle),
+                    "{encoding:?} {needle}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_position_inside_surrogate_pair_rounds_down() {
+        let rope = Rope::from_str(TEXT);
+        let emoji = rope.byte_to_char(TEXT.find('😀').unwrap());
+        assert_eq!(
+            position_to_char_index(&rope, position(0, 10), PositionEncoding::Utf16),
+            emoji
+        );
+    }
+
+    #[test]
+    fn test_position_past_end_of_line_clamps_to_before_newline() {
+        let rope = Rope::from_str(TEXT);
+        let newline = rope.line_to_char(1) - 1;
+        for encoding in [
+            PositionEncoding::Utf8,
+            PositionEncoding::Utf16,
+            PositionEncoding::Utf32,
+        ] {
+            assert_eq!(
+                position_to_char_index(&rope, position(0, 100), encoding),
+                newline,
+                "{encoding:?}"
+            );
+        }
+        assert_eq!(
+            position_to_char_index(&rope, position(5, 0), PositionEncoding::Utf16),
+            rope.len_chars()
+        );
+    }
+
+    #[test]
+    fn test_document_end_position() {
+        let text = "fn f() {}\n// 😀日本";
+        assert_eq!(
+            document_end_position(text, PositionEncoding::Utf8),
+            position(1, 13)
+        );
+        assert_eq!(
+            document_end_position(text, PositionEncoding::Utf16),
+            position(1, 7)
+        );
+        assert_eq!(
+            document_end_position(text, PositionEncoding::Utf32),
+            position(1, 6)
+        );
+    }
+
+    #[test]
+    fn test_lsp_lines_end_at_lone_cr_but_rows_do_not() {
+        let text = "// a\u{c}b\u{2028}c\r\n// \u{b}\u{85}\rdbg!(x)\n";
+        let rope = Rope::from_str(text);
+        let byte = text.find("dbg").unwrap();
+        assert_eq!(point_of(text, "dbg"), Point { row: 1, column: 7 });
+        for encoding in [
+            PositionEncoding::Utf8,
+            PositionEncoding::Utf16,
+            PositionEncoding::Utf32,
+        ] {
+            let position = byte_to_position(&rope, byte, encoding);
+            assert_eq!(position, self::position(2, 0), "{encoding:?}");
+            let char_index = position_to_char_index(&rope, position, encoding);
+            assert_eq!(
+                char_index_to_point(&rope, char_index),
+                point_of(text, "dbg"),
+                "{encoding:?}"
+            );
+        }
+        assert_eq!(
+            byte_to_position(&rope, text.find("c\r\n").unwrap(), PositionEncoding::Utf16),
+            position(0, 7)
+        );
+        assert_eq!(
+            position_to_char_index(&rope, position(1, 100), PositionEncoding::Utf16),
+            rope.byte_to_char(text.rfind('\r').unwrap())
+        );
+        assert_eq!(
+            document_end_position(text, PositionEncoding::Utf16),
+            position(3, 0)
+        );
+        assert_eq!(
+            document_end_position("a\rb\r\nc", PositionEncoding::Utf16),
+            position(2, 1)
+        );
+    }
+}
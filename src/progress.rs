@@ -0,0 +1,87 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use tower_lsp::{
+    lsp_types::{
+        notification::Progress, request::WorkDoneProgressCreate, NumberOrString, ProgressParams,
+        ProgressParamsValue, WorkDoneProgress, WorkDoneProgressBegin, WorkDoneProgressCreateParams,
+        WorkDoneProgressEnd, WorkDoneProgressReport,
+    },
+    Client,
+};
+
+static NEXT_TOKEN: AtomicU32 = AtomicU32::new(0);
+
+/// Server-initiated `$/progress` reporting. Everything is a no-op if the
+/// client doesn't support work done progress.
+pub struct WorkDoneProgressReporter {
+    client: Client,
+    token: Option<NumberOrString>,
+    percentage: AtomicU32,
+}
+
+impl WorkDoneProgressReporter {
+    pub async fn begin(client: Client, supported: bool, title: &str) -> Self {
+        let mut token = None;
+        if supported {
+            let candidate = NumberOrString::String(format!(
+                "tree-sitter-lint/{}",
+                NEXT_TOKEN.fetch_add(1, Ordering::Relaxed)
+            ));
+            if client
+                .send_request::<WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                    token: candidate.clone(),
+                })
+                .await
+                .is_ok()
+            {
+                token = Some(candidate);
+            }
+        }
+        let reporter = Self {
+            client,
+            token,
+            percentage: AtomicU32::new(0),
+        };
+        reporter
+            .notify(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title: title.to_owned(),
+                cancellable: Some(false),
+                message: None,
+                percentage: Some(0),
+            }))
+            .await;
+        reporter
+    }
+
+    /// Reports `done` out of `total` steps, skipping the notification if the
+    /// percentage hasn't moved since the last one.
+    pub async fn report(&self, done: usize, total: usize) {
+        let percentage = (done * 100 / total.max(1)) as u32;
+        if self.percentage.fetch_max(percentage, Ordering::Relaxed) >= percentage {
+            return;
+        }
+        self.notify(WorkDoneProgress::Report(WorkDoneProgressReport {
+            cancellable: Some(false),
+            message: Some(format!("{done}/{total}")),
+            percentage: Some(percentage),
+        }))
+        .await;
+    }
+
+    pub async fn end(self, message: Option<String>) {
+        self.notify(WorkDoneProgress::End(WorkDoneProgressEnd { message }))
+            .await;
+    }
+
+    async fn notify(&self, progress: WorkDoneProgress) {
+        let Some(token) = self.token.clone() else {
+            return;
+        };
+        self.client
+            .send_notification::<Progress>(ProgressParams {
+                token,
+                value: ProgressParamsValue::WorkDone(progress),
+            })
+            .await;
+    }
+}
@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tree_sitter::Node;
+
+use crate::{
+    fixer::Fixer,
+    language::SupportedLanguage,
+    violation::{Edit, RelatedLocation, RuleLevel, Violation, ViolationTag},
+};
+
+pub type OnMatch = for<'a> fn(Node<'a>, &mut QueryMatchContext<'a, '_>);
+
+pub struct Listener {
+    pub query: &'static str,
+    pub on_match: OnMatch,
+}
+
+pub struct Rule {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub docs: Option<&'static str>,
+    pub docs_url: Option<&'static str>,
+    pub fixable: bool,
+    pub level: RuleLevel,
+    /// Attached to every violation the rule reports.
+    pub tags: Vec<ViolationTag>,
+    /// A JSON Schema (see `schema::validate()` for the parts understood)
+    /// describing the rule's options.
+    pub options_schema: Option<Value>,
+    pub languages: Vec<SupportedLanguage>,
+    pub listeners: Vec<Listener>,
+}
+
+pub struct Plugin {
+    pub name: &'static str,
+    pub rules: Vec<Rule>,
+}
+
+/// The parts of a rule's definition that are exposed to the editor, eg when
+/// hovering a violation.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RuleMeta {
+    pub plugin: String,
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub docs: Option<String>,
+    #[serde(default)]
+    pub docs_url: Option<String>,
+    #[serde(default)]
+    pub fixable: bool,
+    #[serde(default)]
+    pub options_schema: Option<Value>,
+    /// Empty if unknown (eg a local binary didn't say), in which case the
+    /// rule is assumed to apply to any language.
+    #[serde(default)]
+    pub languages: Vec<SupportedLanguage>,
+}
+
+impl RuleMeta {
+    pub fn new(plugin: &Plugin, rule: &Rule) -> Self {
+        Self {
+            plugin: plugin.name.to_owned(),
+            name: rule.name.to_owned(),
+            description: rule.description.to_owned(),
+            docs: rule.docs.map(ToOwned::to_owned),
+            docs_url: rule.docs_url.map(ToOwned::to_owned),
+            fixable: rule.fixable,
+            options_schema: rule.options_schema.clone(),
+            languages: rule.languages.clone(),
+        }
+    }
+
+    /// The `default`s given for each option in `options_schema`, if any.
+    pub fn default_options(&self) -> Option<Value> {
+        let defaults = self
+            .options_schema
+            .as_ref()?
+            .get("properties")?
+            .as_object()?
+            .iter()
+            .filter_map(|(key, schema)| Some((key.clone(), schema.get("default")?.clone())))
+            .collect::<serde_json::Map<_, _>>();
+        (!defaults.is_empty()).then_some(Value::Object(defaults))
+    }
+
+    pub fn applies_to(&self, language: SupportedLanguage) -> bool {
+        self.languages.is_empty() || self.languages.contains(&language)
+    }
+}
+
+pub struct QueryMatchContext<'a, 'b> {
+    source: &'a str,
+    plugin: &'b Plugin,
+    rule: &'b Rule,
+    level: RuleLevel,
+    options: &'b Value,
+    violations: &'b mut Vec<Violation>,
+}
+
+impl<'a, 'b> QueryMatchContext<'a, 'b> {
+    pub fn new(
+        source: &'a str,
+        plugin: &'b Plugin,
+        rule: &'b Rule,
+        level: RuleLevel,
+        options: &'b Value,
+        violations: &'b mut Vec<Violation>,
+    ) -> Self {
+        Self {
+            source,
+            plugin,
+            rule,
+            level,
+            options,
+            violations,
+        }
+    }
+
+    pub fn get_node_text(&self, node: Node) -> &'a str {
+        self.get_text(node.byte_range())
+    }
+
+    pub fn get_text(&self, byte_range: std::ops::Range<usize>) -> &'a str {
+        &self.source[byte_range]
+    }
+
+    pub fn options(&self) -> &Value {
+        self.options
+    }
+
+    pub fn report(&mut self, node: Node, message: impl Into<String>) {
+        self.push_violation(node, message.into(), None, vec![]);
+    }
+
+    /// Like `report()`, also pointing at other nodes relevant to the
+    /// violation (each with its own message).
+    pub fn report_with_related<'tree>(
+        &mut self,
+        node: Node,
+        message: impl Into<String>,
+        related: impl IntoIterator<Item = (Node<'tree>, String)>,
+    ) {
+        let related = related
+            .into_iter()
+            .map(|(node, message)| RelatedLocation {
+                range: node.range(),
+                message,
+            })
+            .collect();
+        self.push_violation(node, message.into(), None, related);
+    }
+
+    pub fn report_with_fix(
+        &mut self,
+        node: Node,
+        message: impl Into<String>,
+        fix: impl FnOnce(&mut Fixer),
+    ) {
+        let mut fixer = Fixer::default();
+        fix(&mut fixer);
+        self.push_violation(node, message.into(), Some(fixer.into_edits()), vec![]);
+    }
+
+    fn push_violation(
+        &mut self,
+        node: Node,
+        message: String,
+        fix: Option<Vec<Edit>>,
+        related: Vec<RelatedLocation>,
+    ) {
+        self.violations.push(Violation {
+            plugin: self.plugin.name.to_owned(),
+            rule: self.rule.name.to_owned(),
+            message,
+            range: node.range(),
+            level: self.level,
+            fix,
+            docs_url: self.rule.docs_url.map(ToOwned::to_owned),
+            tags: self.rule.tags.clone(),
+            related,
+        });
+    }
+}
+
+#[macro_export]
+macro_rules! rule {
+    (
+        name => $name:literal,
+        description => $description:literal,
+        $(docs => $docs:literal,)?
+        $(docs_url => $docs_url:literal,)?
+        $(fixable => $fixable:literal,)?
+        $(level => $level:ident,)?
+        $(tags => [$($tag:ident),* $(,)?],)?
+        $(options_schema => $options_schema:expr,)?
+        languages => [$($language:ident),* $(,)?],
+        listeners => [$($query:expr => $on_match:expr),* $(,)?] $(,)?
+    ) => {
+        $crate::rule::Rule {
+            name: $name,
+            description: $description,
+            docs: $crate::rule!(@or_default None $(, Some($docs))?),
+            docs_url: $crate::rule!(@or_default None $(, Some($docs_url))?),
+            fixable: $crate::rule!(@or_default false $(, $fixable)?),
+            level: $crate::rule!(
+                @or_default $crate::violation::RuleLevel::Error
+                $(, $crate::violation::RuleLevel::$level)?
+            ),
+            tags: vec![$($($crate::violation::ViolationTag::$tag),*)?],
+            options_schema: $crate::rule!(@or_default None $(, Some($options_schema))?),
+            languages: vec![$($crate::language::SupportedLanguage::$language),*],
+            listeners: vec![$(
+                $crate::rule::Listener {
+                    query: $query,
+                    on_match: $on_match,
+                }
+            ),*],
+        }
+    };
+    (@or_default $default:expr) => {
+        $default
+    };
+    (@or_default $default:expr, $value:expr) => {
+        $value
+    };
+}
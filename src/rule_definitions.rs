@@ -0,0 +1,72 @@
+//! Finding where rules are defined (with `rule!`) in Rust source, so the
+//! config file can link to them.
+
+use std::sync::OnceLock;
+
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{Node, Query, QueryCursor, Range, Tree};
+
+use crate::{language::SupportedLanguage, lint};
+
+const RULE_MACRO_QUERY: &str = r#"
+  (macro_invocation
+    macro: [
+      (identifier) @_macro
+      (scoped_identifier name: (identifier) @_macro)
+    ]
+    (token_tree) @body
+    (#eq? @_macro "rule"))
+"#;
+
+fn query() -> &'static Query {
+    static QUERY: OnceLock<Query> = OnceLock::new();
+    QUERY.get_or_init(|| Query::new(&SupportedLanguage::Rust.language(), RULE_MACRO_QUERY).unwrap())
+}
+
+/// The ranges of the name (the `"..."` in `name => "..."`) of each `rule!`
+/// in `source` defining a rule called `rule`.
+pub fn find(source: &str, rule: &str) -> Vec<Range> {
+    let literal = format!("\"{rule}\"");
+    if !source.contains("rule!") || !source.contains(&literal) {
+        return vec![];
+    }
+    let Some(tree) = lint::parse(source, SupportedLanguage::Rust) else {
+        return vec![];
+    };
+    rule_bodies(source, &tree)
+        .into_iter()
+        .filter_map(|body| entry(source, body, "name"))
+        .filter(|name| source[name.byte_range()] == literal)
+        .map(|name| name.range())
+        .collect()
+}
+
+/// The bodies (the `{ ... }` token trees) of the `rule!`s in `tree`.
+pub fn rule_bodies<'tree>(source: &str, tree: &'tree Tree) -> Vec<Node<'tree>> {
+    let query = query();
+    let body_index = query.capture_index_for_name("body").unwrap();
+    let mut query_cursor = QueryCursor::new();
+    let mut matches = query_cursor.matches(query, tree.root_node(), source.as_bytes());
+    let mut bodies = vec![];
+    while let Some(query_match) = matches.next() {
+        bodies.extend(
+            query_match
+                .captures
+                .iter()
+                .filter(|capture| capture.index == body_index)
+                .map(|capture| capture.node),
+        );
+    }
+    bodies
+}
+
+/// The value of the `key => value` entry at the top level of a `rule!`'s
+/// `body`, if it's a single token (tree).
+pub fn entry<'tree>(source: &str, body: Node<'tree>, key: &str) -> Option<Node<'tree>> {
+    let mut children = body
+        .children(&mut body.walk())
+        .collect::<Vec<_>>()
+        .into_iter();
+    children.find(|child| child.kind() == "identifier" && &source[child.byte_range()] == key)?;
+    children.find(|child| !matches!(child.kind(), "=" | ">" | "=>"))
+}
@@ -0,0 +1,289 @@
+//! Checks the tree-sitter queries of the listeners in `rule!`s (in Rust
+//! source) against the grammars of the languages the rules are for, to help
+//! when writing rules.
+
+use std::ops;
+
+use tree_sitter::{Node, Query, QueryError, QueryErrorKind, Tree};
+
+use crate::{
+    language::SupportedLanguage,
+    rule_definitions::{entry, rule_bodies},
+    violation::{RuleLevel, Violation},
+};
+
+pub const INVALID_RULE_QUERY: &str = "invalid-rule-query";
+
+/// Reports each problem with a listener's query at the offending part of
+/// its string literal. `tree` is `source` parsed as Rust.
+pub fn check(source: &str, tree: &Tree) -> Vec<Violation> {
+    if !source.contains("rule!") {
+        return vec![];
+    }
+    let mut problems = vec![];
+    for body in rule_bodies(source, tree) {
+        let languages = entry(source, body, "languages")
+            .map(|languages| {
+                languages
+                    .named_children(&mut languages.walk())
+                    .filter_map(|name| {
+                        SupportedLanguage::from_name(&source[name.byte_range()].to_lowercase())
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let Some(listeners) = entry(source, body, "listeners") else {
+            continue;
+        };
+        for literal in query_literals(listeners) {
+            let Some(query) = QueryLiteral::decode(source, literal) else {
+                continue;
+            };
+            for &language in &languages {
+                let Err(error) = Query::new(&language.language(), &query.text) else {
+                    continue;
+                };
+                let range = query.source_range(error_range(&query.text, &error));
+                let message = error_message(&error, language);
+                if !problems.iter().any(|problem: &Violation| {
+                    problem.range.start_byte == range.start && problem.message == message
+                }) {
+                    problems.push(Violation::internal(
+                        source,
+                        INVALID_RULE_QUERY,
+                        RuleLevel::Error,
+                        message,
+                        range,
+                    ));
+                }
+            }
+        }
+    }
+    problems
+}
+
+/// The string literals keying the listeners, ie followed by `=>`.
+fn query_literals(listeners: Node) -> Vec<Node> {
+    let children = listeners
+        .children(&mut listeners.walk())
+        .collect::<Vec<_>>();
+    children
+        .windows(2)
+        .filter(|pair| {
+            matches!(pair[0].kind(), "string_literal" | "raw_string_literal")
+                && matches!(pair[1].kind(), "=>" | "=")
+        })
+        .map(|pair| pair[0])
+        .collect()
+}
+
+/// A query as written in a string literal: its text (with any escapes
+/// decoded) and where each of the text's bytes came from in the source.
+struct QueryLiteral {
+    text: String,
+    source_offsets: Vec<usize>,
+}
+
+impl QueryLiteral {
+    fn decode(source: &str, literal: Node) -> Option<Self> {
+        let mut query = Self {
+            text: String::new(),
+            source_offsets: vec![],
+        };
+        let mut end = None;
+        for part in literal.named_children(&mut literal.walk()) {
+            let text = &source[part.byte_range()];
+            match part.kind() {
+                "string_content" => {
+                    query.text.push_str(text);
+                    query.source_offsets.extend(part.byte_range());
+                }
+                "escape_sequence" => {
+                    let decoded = unescape(text)?;
+                    query.text.push_str(&decoded);
+                    query
+                        .source_offsets
+                        .extend(std::iter::repeat_n(part.start_byte(), decoded.len()));
+                }
+                _ => continue,
+            }
+            end = Some(part.end_byte());
+        }
+        query.source_offsets.push(end?);
+        Some(query)
+    }
+
+    fn source_range(&self, range: ops::Range<usize>) -> ops::Range<usize> {
+        let offset = |index: usize| self.source_offsets[index.min(self.text.len())];
+        let start = offset(range.start);
+        start..offset(range.end).max(start)
+    }
+}
+
+fn unescape(escape: &str) -> Option<String> {
+    let mut chars = escape.strip_prefix('\\')?.chars();
+    let decoded = match chars.next()? {
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        '0' => '\0',
+        '\n' => return Some(String::new()),
+        'x' => char::from(u8::from_str_radix(chars.as_str(), 16).ok()?),
+        'u' => char::from_u32(
+            u32::from_str_radix(
+                chars.as_str().trim_start_matches('{').trim_end_matches('}'),
+                16,
+            )
+            .ok()?,
+        )?,
+        c => c,
+    };
+    Some(decoded.to_string())
+}
+
+/// The part of `query` an error's about: the offending name, or the token
+/// the error was found at (or the last one, at the end).
+fn error_range(query: &str, error: &QueryError) -> ops::Range<usize> {
+    let start = error.offset.min(query.len());
+    if start == query.len() {
+        // Eg an unclosed pattern: the last token rather than past the end.
+        let trimmed = query.trim_end();
+        let token_start = trimmed
+            .rfind(|c: char| c.is_whitespace() || "()[]".contains(c))
+            .map_or(0, |index| index + 1);
+        if token_start < trimmed.len() {
+            return token_start..trimmed.len();
+        }
+        return trimmed.len() - trimmed.chars().last().map_or(0, char::len_utf8)..trimmed.len();
+    }
+    let len = match error.kind {
+        QueryErrorKind::NodeType | QueryErrorKind::Field | QueryErrorKind::Capture => {
+            error.message.len()
+        }
+        _ => query[start..]
+            .find(|c: char| c.is_whitespace() || c == ')' || c == ']')
+            .unwrap_or(query.len() - start)
+            .max(query[start..].chars().next().map_or(0, char::len_utf8)),
+    };
+    start..start + len
+}
+
+fn error_message(error: &QueryError, language: SupportedLanguage) -> String {
+    match error.kind {
+        QueryErrorKind::NodeType => {
+            format!("Invalid node type `{}` for {language}.", error.message)
+        }
+        QueryErrorKind::Field => format!("Invalid field `{}` for {language}.", error.message),
+        QueryErrorKind::Capture => format!("Undefined capture `@{}`.", error.message),
+        QueryErrorKind::Predicate => format!("Invalid predicate: {}", error.message),
+        QueryErrorKind::Structure => format!("Impossible pattern for {language}."),
+        QueryErrorKind::Syntax | QueryErrorKind::Language => "Invalid query syntax.".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint;
+
+    /// The problems with the listeners' queries in a `rule!` for Rust, with
+    /// the source text they're reported at.
+    fn check_listeners(listeners: &str) -> Vec<(String, String)> {
+        let source = format!(
+            "rule! {{\n    name => \"x\",\n    languages => [Rust],\n    listeners => [\n{listeners}\n    ],\n}}\n"
+        );
+        let tree = lint::parse(&source, SupportedLanguage::Rust).unwrap();
+        check(&source, &tree)
+            .into_iter()
+            .map(|problem| {
+                let range = problem.range;
+                let line_start = source[..range.start_byte]
+                    .rfind('\n')
+                    .map_or(0, |index| index + 1);
+                assert_eq!(range.start_point.column, range.start_byte - line_start);
+                (
+                    problem.message,
+                    source[range.start_byte..range.end_byte].to_owned(),
+                )
+            })
+            .collect()
+    }
+
+    fn problem(message: &str, text: &str) -> (String, String) {
+        (message.to_owned(), text.to_owned())
+    }
+
+    #[test]
+    fn test_valid_queries() {
+        assert_eq!(
+            check_listeners(
+                r##"r#"(call_expression) @c"# => |node, context| {},
+        "(function_item name: (identifier) @name)" => |node, context| {},"##
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_invalid_node_type() {
+        assert_eq!(
+            check_listeners(r#""(function_itemz) @f" => |node, context| {},"#),
+            vec![problem(
+                "Invalid node type `function_itemz` for rust.",
+                "function_itemz"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_invalid_field() {
+        assert_eq!(
+            check_listeners(r#""(call_expression functio: (identifier))" => |node, context| {},"#),
+            vec![problem("Invalid field `functio` for rust.", "functio")]
+        );
+    }
+
+    #[test]
+    fn test_undefined_capture() {
+        assert_eq!(
+            check_listeners(r#""((identifier) @a (#eq? @b \"x\"))" => |node, context| {},"#),
+            vec![problem("Undefined capture `@b`.", "b")]
+        );
+    }
+
+    #[test]
+    fn test_raw_string() {
+        assert_eq!(
+            check_listeners(
+                r###"r#"(call_expression "("  (identifierz))"# => |node, context| {},"###
+            ),
+            vec![problem(
+                "Invalid node type `identifierz` for rust.",
+                "identifierz"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_offsets_after_escapes() {
+        assert_eq!(
+            check_listeners(r#""(call_expression \"(\"\n  (identifierz))" => |node, context| {},"#),
+            vec![problem(
+                "Invalid node type `identifierz` for rust.",
+                "identifierz"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_unterminated_pattern() {
+        assert_eq!(
+            check_listeners(r#""(call_expression" => |node, context| {},"#),
+            vec![problem("Invalid query syntax.", "call_expression")]
+        );
+        assert_eq!(
+            check_listeners(r#""(call_expression (identifier)" => |node, context| {},"#),
+            vec![problem("Invalid query syntax.", ")")]
+        );
+    }
+}
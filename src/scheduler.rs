@@ -0,0 +1,141 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use dashmap::{mapref::entry::Entry, DashMap};
+use tokio::task::JoinHandle;
+use tower_lsp::lsp_types::Url;
+
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn is(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+struct LintTask {
+    handle: JoinHandle<()>,
+    cancellation: CancellationToken,
+}
+
+impl LintTask {
+    fn cancel(&self) {
+        self.cancellation.cancel();
+        self.handle.abort();
+    }
+}
+
+/// Runs at most one pending lint per document: scheduling a new lint for a
+/// URI cancels whichever one was previously waiting or running for it.
+#[derive(Default)]
+pub struct LintScheduler {
+    /// Tasks remove themselves once they're done.
+    tasks: Arc<DashMap<Url, LintTask>>,
+}
+
+impl LintScheduler {
+    pub fn schedule<Fut>(
+        &self,
+        uri: Url,
+        delay: Duration,
+        lint: impl FnOnce(CancellationToken) -> Fut,
+    ) where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let cancellation = CancellationToken::default();
+        let lint = lint(cancellation.clone());
+        let tasks = self.tasks.clone();
+        let task_cancellation = cancellation.clone();
+        // Holding the entry means the task can't remove itself before it's
+        // been inserted.
+        let entry = self.tasks.entry(uri.clone());
+        let handle = tokio::spawn(async move {
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            lint.await;
+            tasks.remove_if(&uri, |_, task| task.cancellation.is(&task_cancellation));
+        });
+        let task = LintTask {
+            handle,
+            cancellation,
+        };
+        match entry {
+            Entry::Occupied(mut entry) => entry.insert(task).cancel(),
+            Entry::Vacant(entry) => {
+                entry.insert(task);
+            }
+        }
+    }
+
+    pub fn cancel(&self, uri: &Url) {
+        if let Some((_, task)) = self.tasks.remove(uri) {
+            task.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_only_the_latest_lint_runs() {
+        let scheduler = LintScheduler::default();
+        let uri = Url::parse("file:///a.rs").unwrap();
+        let other_uri = Url::parse("file:///b.rs").unwrap();
+        let runs = Arc::new(Mutex::new(vec![]));
+        let lint = |name: &'static str| {
+            let runs = runs.clone();
+            move |_| async move { runs.lock().unwrap().push(name) }
+        };
+
+        let delay = Duration::from_millis(50);
+        scheduler.schedule(uri.clone(), delay, lint("superseded"));
+        scheduler.schedule(uri.clone(), delay, lint("latest"));
+        scheduler.schedule(other_uri.clone(), delay, lint("cancelled"));
+        scheduler.cancel(&other_uri);
+        tokio::time::sleep(delay * 4).await;
+
+        assert_eq!(*runs.lock().unwrap(), vec!["latest"]);
+        assert!(scheduler.tasks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_superseded_lint_is_cancelled_while_running() {
+        let scheduler = LintScheduler::default();
+        let uri = Url::parse("file:///a.rs").unwrap();
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        scheduler.schedule(
+            uri.clone(),
+            Duration::ZERO,
+            move |cancellation| async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                let _ = sender.send(cancellation);
+            },
+        );
+        let cancellation = scheduler.tasks.get(&uri).unwrap().cancellation.clone();
+        scheduler.schedule(uri, Duration::ZERO, |_| async {});
+
+        assert!(cancellation.is_cancelled());
+        // Aborted, so it never finished.
+        assert!(receiver.await.is_err());
+    }
+}
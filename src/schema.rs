@@ -0,0 +1,210 @@
+//! A small subset of JSON Schema, enough to describe rule options: `type`,
+//! `enum`, `minimum`/`maximum`, `properties`, `required`,
+//! `additionalProperties` and `items`. Anything else is ignored.
+
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaError {
+    /// Where in the validated value the problem is: object keys, or array
+    /// indices as strings.
+    pub path: Vec<String>,
+    pub message: String,
+}
+
+pub fn validate(schema: &Value, value: &Value) -> Vec<SchemaError> {
+    let mut errors = vec![];
+    validate_at(schema, value, &mut vec![], &mut errors);
+    errors
+}
+
+/// The schema describing the value at `path` (object keys only), if known.
+pub fn schema_at<'a>(schema: &'a Value, path: &[&str]) -> Option<&'a Value> {
+    path.iter()
+        .try_fold(schema, |schema, key| schema.get("properties")?.get(*key))
+}
+
+fn validate_at(
+    schema: &Value,
+    value: &Value,
+    path: &mut Vec<String>,
+    errors: &mut Vec<SchemaError>,
+) {
+    let mut error = |message: String| {
+        errors.push(SchemaError {
+            path: path.clone(),
+            message,
+        })
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let types = match expected {
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            expected => expected.as_str().into_iter().collect::<Vec<_>>(),
+        };
+        if !types.is_empty() && !types.iter().any(|&expected| has_type(value, expected)) {
+            error(format!(
+                "Expected {}, found {}.",
+                types.join(" or "),
+                type_name(value)
+            ));
+            return;
+        }
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            let allowed = allowed.iter().map(Value::to_string).collect::<Vec<_>>();
+            error(format!("Expected one of {}.", allowed.join(", ")));
+        }
+    }
+    if let Some(number) = value.as_f64() {
+        if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64) {
+            if number < minimum {
+                error(format!("Must be at least {minimum}."));
+            }
+        }
+        if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64) {
+            if number > maximum {
+                error(format!("Must be at most {maximum}."));
+            }
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for required in schema
+                .get("required")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+            {
+                if !object.contains_key(required) {
+                    error(format!("Missing required option `{required}`."));
+                }
+            }
+            for (key, value) in object {
+                let property_schema = properties
+                    .and_then(|properties| properties.get(key))
+                    .or_else(|| {
+                        schema
+                            .get("additionalProperties")
+                            .filter(|additional| additional.is_object())
+                    });
+                path.push(key.clone());
+                match property_schema {
+                    Some(property_schema) => validate_at(property_schema, value, path, errors),
+                    None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                        errors.push(SchemaError {
+                            path: path.clone(),
+                            message: format!("Unknown option `{key}`."),
+                        });
+                    }
+                    None => {}
+                }
+                path.pop();
+            }
+        }
+        Value::Array(items) => {
+            if let Some(items_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    path.push(index.to_string());
+                    validate_at(items_schema, item, path, errors);
+                    path.pop();
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        expected => type_name(value) == expected,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "max": { "type": "integer", "minimum": 0 },
+                "style": { "enum": ["a", "b"] },
+                "names": { "type": "array", "items": { "type": "string" } },
+            },
+            "additionalProperties": false,
+        })
+    }
+
+    fn error(path: &[&str], message: &str) -> SchemaError {
+        SchemaError {
+            path: path.iter().map(|&key| key.to_owned()).collect(),
+            message: message.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_valid() {
+        assert_eq!(
+            validate(
+                &schema(),
+                &json!({ "max": 3, "style": "b", "names": ["x"] })
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(
+            validate(
+                &schema(),
+                &json!({ "max": -1, "style": "c", "names": ["x", 2], "other": true })
+            ),
+            vec![
+                error(&["max"], "Must be at least 0."),
+                error(&["names", "1"], "Expected string, found number."),
+                error(&["other"], "Unknown option `other`."),
+                error(&["style"], "Expected one of \"a\", \"b\"."),
+            ]
+        );
+        assert_eq!(
+            validate(&schema(), &json!({ "max": 1.5 })),
+            vec![error(&["max"], "Expected integer, found number.")]
+        );
+        assert_eq!(
+            validate(&schema(), &json!("max")),
+            vec![error(&[], "Expected object, found string.")]
+        );
+    }
+
+    #[test]
+    fn test_schema_at() {
+        let schema = schema();
+        assert_eq!(
+            schema_at(&schema, &["max"]),
+            Some(&json!({ "type": "integer", "minimum": 0 }))
+        );
+        assert_eq!(schema_at(&schema, &["max", "x"]), None);
+    }
+}
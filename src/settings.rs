@@ -0,0 +1,174 @@
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{config::ConfiguredLevel, violation::Violation};
+
+/// The section clients keep our settings under, eg for
+/// `workspace/configuration`.
+pub const SECTION: &str = "tree-sitter-lint";
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Settings {
+    pub enable: bool,
+    pub run: Run,
+    pub debounce_ms: u64,
+    /// Overrides any `local_binary` set in config files. Relative to the
+    /// workspace folder.
+    pub local_binary: Option<PathBuf>,
+    /// Lint every file in the workspace in the background, not just open
+    /// documents.
+    pub lint_workspace: bool,
+    /// Levels for rules (keyed by `plugin/rule`) overriding those from
+    /// config files, eg to show a rule CI treats as a warning as a hint.
+    /// They only re-level problems that were reported, so can't turn a rule
+    /// that's `off` in a config file back on.
+    pub severity_overrides: HashMap<String, ConfiguredLevel>,
+}
+
+/// When open documents get linted.
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Run {
+    #[default]
+    OnType,
+    OnSave,
+}
+
+impl Settings {
+    /// Layers `values` (eg `initializationOptions` and then the result of
+    /// `workspace/configuration`) on top of each other, later ones taking
+    /// precedence. Anything that isn't an object is ignored.
+    pub fn merged<'a>(
+        values: impl IntoIterator<Item = &'a Value>,
+    ) -> Result<Self, serde_json::Error> {
+        let mut merged = Value::Object(Default::default());
+        for value in values {
+            merge(&mut merged, value);
+        }
+        serde_json::from_value(merged)
+    }
+
+    /// Re-levels `violations` according to `severity_overrides`, dropping
+    /// those of rules overridden to `off`.
+    pub fn apply_severity_overrides(&self, violations: Vec<Violation>) -> Vec<Violation> {
+        if self.severity_overrides.is_empty() {
+            return violations;
+        }
+        violations
+            .into_iter()
+            .filter_map(|mut violation| {
+                let name = format!("{}/{}", violation.plugin, violation.rule);
+                if let Some(level) = self.severity_overrides.get(&name) {
+                    violation.level = level.rule_level()?;
+                }
+                Some(violation)
+            })
+            .collect()
+    }
+
+    pub fn debounce(&self) -> Duration {
+        Duration::from_millis(self.debounce_ms)
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            run: Default::default(),
+            debounce_ms: 200,
+            local_binary: None,
+            lint_workspace: false,
+            severity_overrides: Default::default(),
+        }
+    }
+}
+
+fn merge(target: &mut Value, value: &Value) {
+    let (Value::Object(target), Value::Object(value)) = (target, value) else {
+        return;
+    };
+    for (key, value) in value {
+        match target.get_mut(key) {
+            Some(existing) if existing.is_object() && value.is_object() => merge(existing, value),
+            _ => {
+                target.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        config::Config,
+        language::SupportedLanguage,
+        lint::{self, Linter},
+        plugins,
+        violation::RuleLevel,
+    };
+
+    #[test]
+    fn test_merged_layers_values() {
+        let settings = Settings::merged(&[
+            json!({"run": "onSave", "severityOverrides": {"rust/no-dbg-macro": "hint"}}),
+            json!(null),
+            json!({"debounceMs": 50, "severityOverrides": {"rust/max-params": "off"}}),
+            json!({"run": "onType"}),
+        ])
+        .unwrap();
+        assert_eq!(settings.run, Run::OnType);
+        assert_eq!(settings.debounce_ms, 50);
+        assert!(settings.enable);
+        let mut overrides = settings.severity_overrides.keys().collect::<Vec<_>>();
+        overrides.sort();
+        assert_eq!(overrides, ["rust/max-params", "rust/no-dbg-macro"]);
+
+        assert!(Settings::merged(&[json!({"run": "sometimes"})]).is_err());
+    }
+
+    fn lint(config: &str, severity_overrides: Value) -> Vec<(String, RuleLevel)> {
+        let source = "fn f() { dbg!(1); todo!(); }\n";
+        let tree = lint::parse(source, SupportedLanguage::Rust).unwrap();
+        let violations = Linter::new(plugins::all()).lint(
+            source,
+            &tree,
+            SupportedLanguage::Rust,
+            &Config::parse(config).unwrap(),
+        );
+        Settings::merged(&[json!({ "severityOverrides": severity_overrides })])
+            .unwrap()
+            .apply_severity_overrides(violations)
+            .into_iter()
+            .map(|violation| (violation.rule, violation.level))
+            .collect()
+    }
+
+    #[test]
+    fn test_severity_overrides() {
+        assert_eq!(
+            lint(
+                "",
+                json!({"rust/no-dbg-macro": "hint", "rust/no-todo-macro": "off"})
+            ),
+            vec![("no-dbg-macro".to_owned(), RuleLevel::Hint)]
+        );
+    }
+
+    #[test]
+    fn test_severity_overrides_cannot_turn_rules_on() {
+        assert_eq!(
+            lint(
+                "rules:\n  rust/no-dbg-macro: off\n  rust/no-todo-macro: off\n",
+                json!({"rust/no-dbg-macro": "error"})
+            ),
+            vec![]
+        );
+    }
+}
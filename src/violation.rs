@@ -0,0 +1,116 @@
+use std::ops;
+
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Point, Range};
+
+use crate::directives::PLUGIN;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleLevel {
+    Error,
+    Warning,
+    #[serde(alias = "info")]
+    Information,
+    Hint,
+}
+
+/// Lets editors render a violation specially, eg fading out unnecessary code.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ViolationTag {
+    Unnecessary,
+    Deprecated,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Edit {
+    #[serde(with = "RangeDef")]
+    pub range: Range,
+    pub replacement: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Violation {
+    pub plugin: String,
+    pub rule: String,
+    pub message: String,
+    #[serde(with = "RangeDef")]
+    pub range: Range,
+    pub level: RuleLevel,
+    pub fix: Option<Vec<Edit>>,
+    #[serde(default)]
+    pub docs_url: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<ViolationTag>,
+    /// Other places in the same document relevant to the violation.
+    #[serde(default)]
+    pub related: Vec<RelatedLocation>,
+}
+
+impl Violation {
+    /// A problem found by the language server itself (eg with a directive),
+    /// reported under the `tree-sitter-lint` plugin at `range` (bytes) of
+    /// `source`.
+    pub fn internal(
+        source: &str,
+        rule: &str,
+        level: RuleLevel,
+        message: String,
+        range: ops::Range<usize>,
+    ) -> Self {
+        Self {
+            plugin: PLUGIN.to_owned(),
+            rule: rule.to_owned(),
+            message,
+            range: byte_range_to_range(source, range),
+            level,
+            fix: None,
+            docs_url: None,
+            tags: vec![],
+            related: vec![],
+        }
+    }
+}
+
+pub fn byte_range_to_range(source: &str, range: ops::Range<usize>) -> Range {
+    Range {
+        start_point: point_at(source, range.start),
+        end_point: point_at(source, range.end),
+        start_byte: range.start,
+        end_byte: range.end,
+    }
+}
+
+fn point_at(source: &str, byte: usize) -> Point {
+    let line_start = source[..byte].rfind('\n').map_or(0, |index| index + 1);
+    Point {
+        row: source[..byte].matches('\n').count(),
+        column: byte - line_start,
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RelatedLocation {
+    #[serde(with = "RangeDef")]
+    pub range: Range,
+    pub message: String,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(remote = "Range")]
+struct RangeDef {
+    start_byte: usize,
+    end_byte: usize,
+    #[serde(with = "PointDef")]
+    start_point: Point,
+    #[serde(with = "PointDef")]
+    end_point: Point,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(remote = "Point")]
+struct PointDef {
+    row: usize,
+    column: usize,
+}
@@ -0,0 +1,59 @@
+use std::path::{Path, PathBuf};
+
+use ignore::WalkBuilder;
+
+/// Lists the files under `root`, skipping anything ignored by `.gitignore`
+/// (or `.ignore`) files along the way.
+pub fn files(root: &Path) -> Vec<PathBuf> {
+    WalkBuilder::new(root)
+        .require_git(false)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_file())
+        })
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+/// Whether `path` is one of `files(root)`, without listing the rest.
+pub fn includes(root: &Path, path: &Path) -> bool {
+    let target = path.to_owned();
+    WalkBuilder::new(root)
+        .require_git(false)
+        .filter_entry(move |entry| target.starts_with(entry.path()))
+        .build()
+        .filter_map(Result::ok)
+        .any(|entry| {
+            entry.path() == path
+                && entry
+                    .file_type()
+                    .is_some_and(|file_type| file_type.is_file())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    #[test]
+    fn test_includes_matches_files() {
+        let root = env::temp_dir().join(format!("workspace-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("target/gen")).unwrap();
+        fs::write(root.join(".gitignore"), "target\n").unwrap();
+        fs::write(root.join("src/a.rs"), "").unwrap();
+        fs::write(root.join("target/gen/b.rs"), "").unwrap();
+
+        assert_eq!(files(&root), vec![root.join("src/a.rs")]);
+        assert!(includes(&root, &root.join("src/a.rs")));
+        assert!(!includes(&root, &root.join("target/gen/b.rs")));
+        assert!(!includes(&root, &root.join("src/missing.rs")));
+        assert!(!includes(&root, &root.join("src")));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}